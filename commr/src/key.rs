@@ -0,0 +1,243 @@
+use feruca::Collator;
+use std::cmp::Ordering;
+
+/// How the keys of the lines are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// By bytes, like `sort` in the C locale
+    Bytes,
+    /// By the leading number, like `sort -n`
+    Numeric,
+    /// By the version numbers within, like `sort -V`
+    Version,
+    /// By the Unicode Collation Algorithm with the CLDR root order
+    Collate,
+}
+
+/// A line as read, and the key it is compared by when that differs
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub text: String,
+    key: Option<String>,
+}
+
+impl Line {
+    pub fn key(&self) -> &str {
+        self.key.as_deref().unwrap_or(&self.text)
+    }
+}
+
+/// Compares lines by their keys, leaving the lines themselves untouched
+pub struct Comparator {
+    order: Order,
+    fold_case: bool,
+    collator: Collator,
+}
+
+impl Comparator {
+    pub fn new(order: Order, fold_case: bool) -> Self {
+        Comparator {
+            order,
+            fold_case,
+            collator: Collator::default(),
+        }
+    }
+
+    /// Folds case to upper, as `sort -f` does, so both agree on where
+    /// letters go among the punctuation
+    pub fn line(&self, text: String) -> Line {
+        let key = if self.fold_case {
+            Some(text.to_uppercase())
+        } else {
+            None
+        };
+        Line { text, key }
+    }
+
+    /// Like `sort`, keys that tie are told apart by their bytes, so only
+    /// identical keys are equal
+    pub fn compare(&mut self, a: &Line, b: &Line) -> Ordering {
+        let (a, b) = (a.key(), b.key());
+        match self.order {
+            Order::Bytes => a.cmp(b),
+            Order::Numeric => Number::parse(a).cmp(&Number::parse(b)).then(a.cmp(b)),
+            Order::Version => version_cmp(a.as_bytes(), b.as_bytes()).then(a.cmp(b)),
+            Order::Collate => self.collator.collate(a, b),
+        }
+    }
+}
+
+/// The leading number of a line, with the leading zeros of the integer and
+/// the trailing zeros of the fraction removed, so the digits compare as
+/// text. A line that does not start with a number counts as zero
+#[derive(Debug, PartialEq, Eq)]
+struct Number<'a> {
+    negative: bool,
+    int: &'a str,
+    frac: &'a str,
+}
+
+impl<'a> Number<'a> {
+    fn parse(line: &'a str) -> Self {
+        let line = line.trim_start_matches([' ', '\t']);
+        let (negative, rest) = match line.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let int_len = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (int, rest) = rest.split_at(int_len);
+        let frac = match rest.strip_prefix('.') {
+            Some(rest) => {
+                &rest[..rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len())]
+            }
+            None => "",
+        };
+        let int = int.trim_start_matches('0');
+        let frac = frac.trim_end_matches('0');
+        Number {
+            // There is no negative zero
+            negative: negative && !(int.is_empty() && frac.is_empty()),
+            int,
+            frac,
+        }
+    }
+}
+
+impl Ord for Number<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (negative, _) => {
+                let magnitude = self
+                    .int
+                    .len()
+                    .cmp(&other.int.len())
+                    .then(self.int.cmp(other.int))
+                    .then(self.frac.cmp(other.frac));
+                if negative {
+                    magnitude.reverse()
+                } else {
+                    magnitude
+                }
+            }
+        }
+    }
+}
+
+impl PartialOrd for Number<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compare as `sort -V` does: hidden files first, then by the names
+/// without their suffixes, such as `.tar.gz`, and only then with them
+fn version_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    match (a, b) {
+        ([], []) => return Ordering::Equal,
+        ([], _) => return Ordering::Less,
+        (_, []) => return Ordering::Greater,
+        _ => (),
+    }
+    match (a[0] == b'.', b[0] == b'.') {
+        (true, false) => return Ordering::Less,
+        (false, true) => return Ordering::Greater,
+        // "." and ".." go before the other hidden files
+        (true, true) => {
+            for special in [&b"."[..], b".."] {
+                match (a == special, b == special) {
+                    (true, true) => return Ordering::Equal,
+                    (true, false) => return Ordering::Less,
+                    (false, true) => return Ordering::Greater,
+                    (false, false) => (),
+                }
+            }
+        }
+        (false, false) => (),
+    }
+
+    let (a_prefix, b_prefix) = (&a[..prefix_len(a)], &b[..prefix_len(b)]);
+    match verrevcmp(a_prefix, b_prefix) {
+        Ordering::Equal if a_prefix.len() < a.len() || b_prefix.len() < b.len() => verrevcmp(a, b),
+        ord => ord,
+    }
+}
+
+/// The length of a name without its suffix, which is made of any number of
+/// `.` followed by a letter or `~` and then letters, digits or `~`
+fn prefix_len(s: &[u8]) -> usize {
+    let is_suffix_start = |c: u8| c.is_ascii_alphabetic() || c == b'~';
+    let is_suffix = |c: u8| c.is_ascii_alphanumeric() || c == b'~';
+    let mut prefix_len = 0;
+    let mut i = 0;
+    loop {
+        // A hidden file can be all suffix
+        while i + 1 < s.len() && s[i] == b'.' && is_suffix_start(s[i + 1]) {
+            i += 2;
+            while i < s.len() && is_suffix(s[i]) {
+                i += 1;
+            }
+        }
+        if i == s.len() {
+            return prefix_len;
+        }
+        i += 1;
+        prefix_len = i;
+    }
+}
+
+/// Where a character goes outside a number: the end and `~` go before
+/// anything, then letters, then everything else
+fn char_order(s: &[u8], pos: usize) -> i32 {
+    match s.get(pos) {
+        None => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => i32::from(*c),
+        Some(b'~') => -2,
+        Some(c) => i32::from(*c) + 256,
+    }
+}
+
+/// The Debian comparison of version strings, which alternates between runs
+/// of other characters and numbers compared by their value
+fn verrevcmp(a: &[u8], b: &[u8]) -> Ordering {
+    let is_digit_at = |s: &[u8], pos: usize| s.get(pos).is_some_and(u8::is_ascii_digit);
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !a[i].is_ascii_digit()) || (j < b.len() && !b[j].is_ascii_digit()) {
+            let ord = char_order(a, i).cmp(&char_order(b, j));
+            if ord != Ordering::Equal {
+                return ord;
+            }
+            i += 1;
+            j += 1;
+        }
+        while i < a.len() && a[i] == b'0' {
+            i += 1;
+        }
+        while j < b.len() && b[j] == b'0' {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while is_digit_at(a, i) && is_digit_at(b, j) {
+            first_diff = first_diff.then(a[i].cmp(&b[j]));
+            i += 1;
+            j += 1;
+        }
+        if is_digit_at(a, i) {
+            return Ordering::Greater;
+        }
+        if is_digit_at(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
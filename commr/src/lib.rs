@@ -1,11 +1,14 @@
+mod key;
+
 use std::{
     cmp::Ordering,
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, Lines},
 };
 
 use crate::Column::*;
 use clap::{App, Arg};
+use key::{Comparator, Line, Order};
 
 type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
@@ -13,11 +16,13 @@ type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 pub struct Config {
     file1: String,
     file2: String,
-    show_col1: bool,
-    show_col2: bool,
-    show_col3: bool,
+    suppress_col1: bool,
+    suppress_col2: bool,
+    suppress_col3: bool,
     insensitive: bool,
+    order: Order,
     delimiter: String,
+    check_order: bool,
 }
 
 enum Column<'a> {
@@ -46,21 +51,21 @@ pub fn get_args() -> MyResult<Config> {
                 .required(true),
         )
         .arg(
-            Arg::with_name("show_col1")
+            Arg::with_name("suppress_col1")
                 .short("1")
-                .help("Show printing of column 1")
+                .help("Suppress printing of column 1")
                 .takes_value(false),
         )
         .arg(
-            Arg::with_name("show_col2")
+            Arg::with_name("suppress_col2")
                 .short("2")
-                .help("Show printing of column 2")
+                .help("Suppress printing of column 2")
                 .takes_value(false),
         )
         .arg(
-            Arg::with_name("show_col3")
+            Arg::with_name("suppress_col3")
                 .short("3")
-                .help("Show printing of column 3")
+                .help("Suppress printing of column 3")
                 .takes_value(false),
         )
         .arg(
@@ -70,6 +75,25 @@ pub fn get_args() -> MyResult<Config> {
                 .long("insensitive")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("numeric")
+                .help("Compare lines by their leading number, as sort -n")
+                .short("n")
+                .long("numeric")
+                .conflicts_with_all(&["version_sort", "collate"]),
+        )
+        .arg(
+            Arg::with_name("version_sort")
+                .help("Compare version numbers within lines, as sort -V")
+                .long("version-sort")
+                .conflicts_with_all(&["numeric", "collate"]),
+        )
+        .arg(
+            Arg::with_name("collate")
+                .help("Compare lines with the Unicode Collation Algorithm")
+                .long("collate")
+                .conflicts_with_all(&["numeric", "version_sort"]),
+        )
         .arg(
             Arg::with_name("delimiter")
                 .help("Output delimiter")
@@ -78,26 +102,50 @@ pub fn get_args() -> MyResult<Config> {
                 .value_name("DELIM")
                 .default_value("\t"),
         )
+        .arg(
+            Arg::with_name("check_order")
+                .help("Fail on the first line that is not in sorted order")
+                .long("check-order")
+                .overrides_with("nocheck_order"),
+        )
+        .arg(
+            Arg::with_name("nocheck_order")
+                .help("Do not check that the input is sorted")
+                .long("nocheck-order")
+                .overrides_with("check_order"),
+        )
         .get_matches();
 
     let file1 = matches.value_of("file1").unwrap().to_owned();
     let file2 = matches.value_of("file2").unwrap().to_owned();
 
-    let show_col1 = matches.is_present("show_col1");
-    let show_col2 = matches.is_present("show_col2");
-    let show_col3 = matches.is_present("show_col3");
+    let suppress_col1 = matches.is_present("suppress_col1");
+    let suppress_col2 = matches.is_present("suppress_col2");
+    let suppress_col3 = matches.is_present("suppress_col3");
 
     let insensitive = matches.is_present("insensitive");
+    let order = if matches.is_present("numeric") {
+        Order::Numeric
+    } else if matches.is_present("version_sort") {
+        Order::Version
+    } else if matches.is_present("collate") {
+        Order::Collate
+    } else {
+        Order::Bytes
+    };
     let delimiter = matches.value_of("delimiter").unwrap().to_owned();
+    let check_order = matches.is_present("check_order");
 
     Ok(Config {
         file1,
         file2,
-        show_col1,
-        show_col2,
-        show_col3,
+        suppress_col1,
+        suppress_col2,
+        suppress_col3,
         insensitive,
+        order,
         delimiter,
+        check_order,
     })
 }
 
@@ -110,78 +158,53 @@ pub fn run(config: Config) -> MyResult<()> {
         return Err(From::from("Both input files cannot be STDIN (\"-\")"));
     }
 
-    let case = |line: String| {
-        if config.insensitive {
-            line.to_lowercase()
-        } else {
-            line
-        }
-    };
+    let mut cmp = Comparator::new(config.order, config.insensitive);
 
+    // Each column is indented by one delimiter for every column shown
+    // before it
     let print = |col: Column| {
-        let mut columns = vec![];
-        match col {
-            Col1(val) => {
-                if config.show_col1 || (!config.show_col2 && !config.show_col3) {
-                    columns.push(val);
-                }
-            }
-            Col2(val) => {
-                if config.show_col2 || (!config.show_col1 && !config.show_col3) {
-                    if config.show_col1 || (!config.show_col2 && !config.show_col3) {
-                        columns.push("");
-                    }
-                    columns.push(val);
-                }
-            }
-            Col3(val) => {
-                if config.show_col3 || (!config.show_col1 && !config.show_col2) {
-                    if config.show_col1 || (!config.show_col2 && !config.show_col3) {
-                        columns.push("");
-                    }
-                    if config.show_col2 || (!config.show_col1 && !config.show_col3) {
-                        columns.push("");
-                    }
-                    columns.push(val);
-                }
-            }
-        }
-
-        if !columns.is_empty() {
-            println!("{}", columns.join(&config.delimiter));
-        }
+        let (indent, val) = match col {
+            Col1(val) if !config.suppress_col1 => (0, val),
+            Col2(val) if !config.suppress_col2 => (usize::from(!config.suppress_col1), val),
+            Col3(val) if !config.suppress_col3 => (
+                usize::from(!config.suppress_col1) + usize::from(!config.suppress_col2),
+                val,
+            ),
+            _ => return,
+        };
+        println!("{}{}", config.delimiter.repeat(indent), val);
     };
 
-    let mut file1_lines = open(file1)?.lines().filter_map(Result::ok).map(case);
-    let mut file2_lines = open(file2)?.lines().filter_map(Result::ok).map(case);
+    let mut file1_lines = Input::new(file1, open(file1)?, config.check_order);
+    let mut file2_lines = Input::new(file2, open(file2)?, config.check_order);
 
-    let mut line1 = file1_lines.next();
-    let mut line2 = file2_lines.next();
+    let mut line1 = file1_lines.next(&mut cmp)?;
+    let mut line2 = file2_lines.next(&mut cmp)?;
 
     while line1.is_some() || line2.is_some() {
         match (&line1, &line2) {
-            (Some(val1), Some(val2)) => match val1.cmp(val2) {
+            (Some(val1), Some(val2)) => match cmp.compare(val1, val2) {
                 Ordering::Equal => {
-                    print(Col3(val1));
-                    line1 = file1_lines.next();
-                    line2 = file2_lines.next();
+                    print(Col3(&val1.text));
+                    line1 = file1_lines.next(&mut cmp)?;
+                    line2 = file2_lines.next(&mut cmp)?;
                 }
                 Ordering::Greater => {
-                    print(Col2(val2));
-                    line2 = file2_lines.next();
+                    print(Col2(&val2.text));
+                    line2 = file2_lines.next(&mut cmp)?;
                 }
                 Ordering::Less => {
-                    print(Col1(val1));
-                    line1 = file1_lines.next();
+                    print(Col1(&val1.text));
+                    line1 = file1_lines.next(&mut cmp)?;
                 }
             },
             (Some(val1), None) => {
-                print(Col1(val1));
-                line1 = file1_lines.next();
+                print(Col1(&val1.text));
+                line1 = file1_lines.next(&mut cmp)?;
             }
             (None, Some(val2)) => {
-                print(Col2(val2));
-                line2 = file2_lines.next();
+                print(Col2(&val2.text));
+                line2 = file2_lines.next(&mut cmp)?;
             }
             _ => (),
         }
@@ -198,3 +221,43 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
         ))),
     }
 }
+
+/// The lines of one input file, optionally checking that they are sorted
+struct Input<'a> {
+    filename: &'a str,
+    lines: Lines<Box<dyn BufRead>>,
+    check_order: bool,
+    line_num: usize,
+    prev: Option<Line>,
+}
+
+impl<'a> Input<'a> {
+    fn new(filename: &'a str, file: Box<dyn BufRead>, check_order: bool) -> Self {
+        Input {
+            filename,
+            lines: file.lines(),
+            check_order,
+            line_num: 0,
+            prev: None,
+        }
+    }
+
+    fn next(&mut self, cmp: &mut Comparator) -> MyResult<Option<Line>> {
+        let line = match self.lines.next() {
+            Some(line) => cmp.line(line.map_err(|e| format!("{}: {}", self.filename, e))?),
+            None => return Ok(None),
+        };
+        self.line_num += 1;
+
+        if self.check_order {
+            if matches!(&self.prev, Some(prev) if cmp.compare(prev, &line) == Ordering::Greater) {
+                return Err(From::from(format!(
+                    "{}:{}: is not in sorted order: {}",
+                    self.filename, self.line_num, line.text
+                )));
+            }
+            self.prev = Some(line.clone());
+        }
+        Ok(Some(line))
+    }
+}
@@ -1,3 +1,6 @@
+// The tests keep the `.args(&[..])` style they were written in
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -8,6 +11,14 @@ const EMPTY: &str = "tests/inputs/empty.txt";
 const FILE1: &str = "tests/inputs/file1.txt";
 const FILE2: &str = "tests/inputs/file2.txt";
 const BLANK: &str = "tests/inputs/blank.txt";
+const UNSORTED: &str = "tests/inputs/unsorted.txt";
+const UPPER: &str = "tests/inputs/upper.txt";
+const NUMBERS1: &str = "tests/inputs/numbers1.txt";
+const NUMBERS2: &str = "tests/inputs/numbers2.txt";
+const VERSIONS1: &str = "tests/inputs/versions1.txt";
+const VERSIONS2: &str = "tests/inputs/versions2.txt";
+const WORDS1: &str = "tests/inputs/words1.txt";
+const WORDS2: &str = "tests/inputs/words2.txt";
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -179,6 +190,12 @@ fn file1_file2_123() -> TestResult {
 
 // --------------------------------------------------
 // insensitive
+// --------------------------------------------------
+#[test]
+fn file1_file2_i() -> TestResult {
+    run(&["-i", FILE1, FILE2], "tests/expected/file1_file2.i.out")
+}
+
 // --------------------------------------------------
 #[test]
 fn file1_file2_1_i() -> TestResult {
@@ -246,9 +263,9 @@ fn file1_file2_123_i() -> TestResult {
 #[test]
 fn stdin_file1() -> TestResult {
     run_stdin(
-        &["-123", "-i", "-", FILE2],
+        &["-i", "-", FILE2],
         FILE1,
-        "tests/expected/file1_file2.123.i.out",
+        "tests/expected/file1_file2.i.out",
     )
 }
 
@@ -256,9 +273,9 @@ fn stdin_file1() -> TestResult {
 #[test]
 fn stdin_file2() -> TestResult {
     run_stdin(
-        &["-123", "-i", FILE1, "-"],
+        &["-i", FILE1, "-"],
         FILE2,
-        "tests/expected/file1_file2.123.i.out",
+        "tests/expected/file1_file2.i.out",
     )
 }
 
@@ -340,6 +357,133 @@ fn blank_file1() -> TestResult {
     run(&[BLANK, FILE1], "tests/expected/blank_file1.out")
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_check_order_file1() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", UNSORTED, FILE2])
+        .assert()
+        .failure()
+        .stdout("\tB\na\n\tc\nd\n")
+        .stderr(predicate::str::contains(format!(
+            "{}:3: is not in sorted order: c",
+            UNSORTED
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_check_order_file2() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", FILE1, UNSORTED])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{}:3: is not in sorted order: c",
+            UNSORTED
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn check_order_sorted() -> TestResult {
+    run(
+        &["--check-order", FILE1, FILE2],
+        "tests/expected/file1_file2.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn nocheck_order() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", "--nocheck-order", UNSORTED, FILE2])
+        .assert()
+        .success()
+        .stdout("\tB\na\n\tc\nd\nc\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file1_upper_i() -> TestResult {
+    run(&["-i", FILE1, UPPER], "tests/expected/file1_upper.i.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn numeric() -> TestResult {
+    run(
+        &["--numeric", "--check-order", NUMBERS1, NUMBERS2],
+        "tests/expected/numbers.n.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_numeric_unsorted() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--check-order", NUMBERS1, NUMBERS2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "{}:3: is not in sorted order: 010",
+            NUMBERS1
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn version() -> TestResult {
+    run(
+        &["--version-sort", "--check-order", VERSIONS1, VERSIONS2],
+        "tests/expected/versions.V.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn collate() -> TestResult {
+    run(
+        &["--collate", "--check-order", WORDS1, WORDS2],
+        "tests/expected/words.collate.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn collate_i() -> TestResult {
+    run(
+        &["--collate", "-i", "--check-order", WORDS1, WORDS2],
+        "tests/expected/words.collate.i.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_numeric_version_sort() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--numeric", "--version-sort", FILE1, FILE2])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn prints_version() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout("commr 0.1.0\n");
+    Ok(())
+}
+
 //// --------------------------------------------------
 //#[test]
 //fn file1_blanks() -> TestResult {
@@ -0,0 +1,456 @@
+use crate::walk::Entry;
+use crate::MyResult;
+use chrono::{DateTime, Local};
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+/// The `%T` fields that `-printf` understands, as in strftime
+const TIME_FIELDS: &str = "YymdHMSjab+";
+
+/// Where the actions write, and whether any of them has failed
+pub struct Context {
+    out: Box<dyn Write>,
+    failed: bool,
+    prune: bool,
+}
+
+impl Context {
+    pub fn new(out: Box<dyn Write>) -> Self {
+        Context {
+            out,
+            failed: false,
+            prune: false,
+        }
+    }
+
+    /// Whether `-prune` was reached for the current entry, resetting it
+    /// for the next one
+    pub fn take_prune(&mut self) -> bool {
+        mem::take(&mut self.prune)
+    }
+
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Remember to exit with a failure status
+    pub fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    /// Report an error that should not stop the walk
+    pub fn error(&mut self, msg: impl fmt::Display) {
+        eprintln!("{}", msg);
+        self.fail();
+    }
+
+    pub fn out(&mut self) -> &mut dyn Write {
+        self.out.as_mut()
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// An expression primary with side effects, true when it succeeds
+#[derive(Debug)]
+pub enum Action {
+    Print,
+    Print0,
+    Printf(String, Vec<Directive>),
+    Delete,
+    /// `-prune`, which keeps the walk out of the current directory
+    Prune,
+    /// `-exec CMD ;`, run once per entry with `{}` replaced by its path
+    Exec(Vec<String>),
+    /// `-exec CMD {} +`, run with as many paths at a time as ARG_MAX allows
+    ExecBatch(Batch),
+}
+
+/// A piece of a `-printf` format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Directive {
+    Text(String),
+    /// `%p`
+    Path,
+    /// `%f`
+    Name,
+    /// `%h`
+    Dir,
+    /// `%d`
+    Depth,
+    /// `%s`
+    Size,
+    /// `%m`
+    Mode,
+    /// `%u`
+    User,
+    /// `%g`
+    Group,
+    /// `%Tk`, the modification time formatted with the strftime field `k`
+    Time(char),
+}
+
+impl Directive {
+    fn needs_metadata(&self) -> bool {
+        matches!(
+            self,
+            Directive::Size
+                | Directive::Mode
+                | Directive::User
+                | Directive::Group
+                | Directive::Time(_)
+        )
+    }
+}
+
+/// The paths waiting for an `-exec ... +` command
+#[derive(Debug)]
+pub struct Batch {
+    command: Vec<String>,
+    paths: Vec<PathBuf>,
+    size: usize,
+    limit: usize,
+}
+
+impl Batch {
+    pub fn new(command: Vec<String>) -> Self {
+        let limit = arg_max()
+            .saturating_sub(env_size())
+            .saturating_sub(command.iter().map(|arg| arg_size(arg.len())).sum())
+            .saturating_sub(2048);
+        Batch {
+            command,
+            paths: vec![],
+            size: 0,
+            limit,
+        }
+    }
+
+    fn push(&mut self, path: &Path, ctx: &mut Context) -> io::Result<()> {
+        let size = arg_size(path.as_os_str().len());
+        if !self.paths.is_empty() && self.size + size > self.limit {
+            self.run(ctx)?;
+        }
+        self.paths.push(path.to_path_buf());
+        self.size += size;
+        Ok(())
+    }
+
+    fn run(&mut self, ctx: &mut Context) -> io::Result<()> {
+        if self.paths.is_empty() {
+            return Ok(());
+        }
+        ctx.flush()?;
+        let paths = mem::take(&mut self.paths);
+        self.size = 0;
+        match Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .args(paths)
+            .status()
+        {
+            Ok(status) if !status.success() => ctx.fail(),
+            Ok(_) => {}
+            Err(e) => ctx.error(format!("{}: {}", self.command[0], e)),
+        }
+        Ok(())
+    }
+}
+
+impl Action {
+    pub fn apply(&mut self, entry: &Entry, ctx: &mut Context) -> io::Result<bool> {
+        match self {
+            Action::Print => {
+                ctx.out.write_all(entry.path().as_os_str().as_bytes())?;
+                ctx.out.write_all(b"\n")?;
+                Ok(true)
+            }
+            Action::Print0 => {
+                ctx.out.write_all(entry.path().as_os_str().as_bytes())?;
+                ctx.out.write_all(b"\0")?;
+                Ok(true)
+            }
+            Action::Printf(_, directives) => printf(entry, directives, ctx),
+            Action::Delete => Ok(delete(entry, ctx)),
+            Action::Prune => {
+                ctx.prune = true;
+                Ok(true)
+            }
+            Action::Exec(command) => {
+                ctx.flush()?;
+                let path = entry.path().as_os_str();
+                let args: Vec<OsString> = command
+                    .iter()
+                    .map(|arg| {
+                        let mut parts = arg.split("{}");
+                        let mut replaced = OsString::from(parts.next().unwrap_or_default());
+                        for part in parts {
+                            replaced.push(path);
+                            replaced.push(part);
+                        }
+                        replaced
+                    })
+                    .collect();
+                match Command::new(&args[0]).args(&args[1..]).status() {
+                    // Still false, so that -o can try something else
+                    Ok(status) => {
+                        if !status.success() {
+                            ctx.fail();
+                        }
+                        Ok(status.success())
+                    }
+                    Err(e) => {
+                        ctx.error(format!("{}: {}", command[0], e));
+                        Ok(false)
+                    }
+                }
+            }
+            Action::ExecBatch(batch) => {
+                batch.push(entry.path(), ctx)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Run whatever is still pending once the walk is over
+    pub fn finish(&mut self, ctx: &mut Context) -> io::Result<()> {
+        match self {
+            Action::ExecBatch(batch) => batch.run(ctx),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Print => write!(f, "-print"),
+            Action::Print0 => write!(f, "-print0"),
+            Action::Printf(format, _) => write!(f, "-printf {}", format),
+            Action::Delete => write!(f, "-delete"),
+            Action::Prune => write!(f, "-prune"),
+            Action::Exec(command) => write!(f, "-exec {} ;", command.join(" ")),
+            Action::ExecBatch(batch) => write!(f, "-exec {} {{}} +", batch.command.join(" ")),
+        }
+    }
+}
+
+fn printf(entry: &Entry, directives: &[Directive], ctx: &mut Context) -> io::Result<bool> {
+    let meta = if directives.iter().any(Directive::needs_metadata) {
+        match entry.metadata() {
+            Ok(meta) => Some(meta),
+            Err(e) => {
+                ctx.error(format!("{}: {}", entry.path().display(), e));
+                return Ok(false);
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut line: Vec<u8> = vec![];
+    for directive in directives {
+        format_directive(&mut line, directive, entry, meta.as_ref())?;
+    }
+    ctx.out.write_all(&line)?;
+    Ok(true)
+}
+
+fn format_directive(
+    line: &mut Vec<u8>,
+    directive: &Directive,
+    entry: &Entry,
+    meta: Option<&Metadata>,
+) -> io::Result<()> {
+    match (directive, meta) {
+        (Directive::Text(text), _) => line.extend_from_slice(text.as_bytes()),
+        (Directive::Path, _) => line.extend_from_slice(entry.path().as_os_str().as_bytes()),
+        (Directive::Name, _) => line.extend_from_slice(entry.file_name().as_bytes()),
+        (Directive::Dir, _) => match entry.path().parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => {
+                line.extend_from_slice(dir.as_os_str().as_bytes())
+            }
+            _ => line.push(b'.'),
+        },
+        (Directive::Depth, _) => write!(line, "{}", entry.depth())?,
+        (Directive::Size, Some(meta)) => write!(line, "{}", meta.len())?,
+        (Directive::Mode, Some(meta)) => write!(line, "{:o}", meta.mode() & 0o7777)?,
+        (Directive::User, Some(meta)) => match users::get_user_by_uid(meta.uid()) {
+            Some(user) => line.extend_from_slice(user.name().as_bytes()),
+            None => write!(line, "{}", meta.uid())?,
+        },
+        (Directive::Group, Some(meta)) => match users::get_group_by_gid(meta.gid()) {
+            Some(group) => line.extend_from_slice(group.name().as_bytes()),
+            None => write!(line, "{}", meta.gid())?,
+        },
+        (Directive::Time(field), Some(meta)) => {
+            let modified: DateTime<Local> = DateTime::from(meta.modified()?);
+            let format = match field {
+                '+' => "%Y-%m-%d+%H:%M:%S".to_string(),
+                _ => format!("%{}", field),
+            };
+            write!(line, "{}", modified.format(&format))?
+        }
+        (_, None) => {}
+    }
+    Ok(())
+}
+
+/// Remove one entry. Directories are only removed once empty, which
+/// relies on the walk visiting their contents first, and symlinks are
+/// removed rather than followed
+fn delete(entry: &Entry, ctx: &mut Context) -> bool {
+    let path = entry.path();
+    if entry.depth() == 0 {
+        // Like find, quietly leave "." alone, but never touch "/" or ".."
+        if path == Path::new(".") {
+            return true;
+        }
+        if path.parent().is_none() || path.components().next_back() == Some(Component::ParentDir) {
+            ctx.error(format!("Refusing to delete \"{}\"", path.display()));
+            return false;
+        }
+    }
+
+    let result = if entry.file_type().is_dir() {
+        fs::remove_dir(path)
+    } else {
+        fs::remove_file(path)
+    };
+    match result {
+        Ok(_) => true,
+        Err(e) => {
+            ctx.error(format!("{}: {}", path.display(), e));
+            false
+        }
+    }
+}
+
+/// Split a `-printf` format into literal text and directives
+pub fn parse_printf(format: &str) -> MyResult<Vec<Directive>> {
+    let mut directives = vec![];
+    let mut text = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('t') => text.push('\t'),
+                Some('0') => text.push('\0'),
+                Some('\\') => text.push('\\'),
+                Some(c) => return Err(From::from(format!("Invalid -printf escape \"\\{}\"", c))),
+                None => return Err(From::from("Invalid -printf escape \"\\\"")),
+            },
+            '%' => {
+                let directive = match chars.next() {
+                    Some('%') => {
+                        text.push('%');
+                        continue;
+                    }
+                    Some('p') => Directive::Path,
+                    Some('f') => Directive::Name,
+                    Some('h') => Directive::Dir,
+                    Some('d') => Directive::Depth,
+                    Some('s') => Directive::Size,
+                    Some('m') => Directive::Mode,
+                    Some('u') => Directive::User,
+                    Some('g') => Directive::Group,
+                    Some('T') => match chars.next() {
+                        Some(field) if TIME_FIELDS.contains(field) => Directive::Time(field),
+                        field => {
+                            return Err(From::from(format!(
+                                "Invalid -printf directive \"%T{}\"",
+                                field.map(String::from).unwrap_or_default()
+                            )))
+                        }
+                    },
+                    c => {
+                        return Err(From::from(format!(
+                            "Invalid -printf directive \"%{}\"",
+                            c.map(String::from).unwrap_or_default()
+                        )))
+                    }
+                };
+                if !text.is_empty() {
+                    directives.push(Directive::Text(mem::take(&mut text)));
+                }
+                directives.push(directive);
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        directives.push(Directive::Text(text));
+    }
+    Ok(directives)
+}
+
+/// The space one argument takes up: its bytes, the terminating NUL and
+/// the pointer to it in argv
+fn arg_size(len: usize) -> usize {
+    len + 1 + mem::size_of::<usize>()
+}
+
+fn arg_max() -> usize {
+    // SAFETY: sysconf only reads a system limit
+    match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+        max if max > 0 => max as usize,
+        _ => 128 * 1024,
+    }
+}
+
+/// The space the environment takes up, since it shares the limit with argv
+fn env_size() -> usize {
+    std::env::vars_os()
+        .map(|(key, value)| arg_size(key.len() + 1 + value.len()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_printf, Directive};
+
+    #[test]
+    fn test_parse_printf() {
+        assert_eq!(
+            parse_printf("%p %s\\n").unwrap(),
+            vec![
+                Directive::Path,
+                Directive::Text(" ".to_string()),
+                Directive::Size,
+                Directive::Text("\n".to_string()),
+            ]
+        );
+        assert_eq!(
+            parse_printf("%%%f\\t%m%u%TY\\0").unwrap(),
+            vec![
+                Directive::Text("%".to_string()),
+                Directive::Name,
+                Directive::Text("\t".to_string()),
+                Directive::Mode,
+                Directive::User,
+                Directive::Time('Y'),
+                Directive::Text("\0".to_string()),
+            ]
+        );
+        assert_eq!(parse_printf("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn test_parse_printf_errors() {
+        let error = |format| parse_printf(format).unwrap_err().to_string();
+        assert_eq!(error("%z"), "Invalid -printf directive \"%z\"");
+        assert_eq!(error("100%"), "Invalid -printf directive \"%\"");
+        assert_eq!(error("%TQ"), "Invalid -printf directive \"%TQ\"");
+        assert_eq!(error("%T"), "Invalid -printf directive \"%T\"");
+        assert_eq!(error("\\q"), "Invalid -printf escape \"\\q\"");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::action::Context;
+use crate::walk::Entry;
+use crate::MyResult;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// How much of each file is hashed before reading any of them in full
+const PARTIAL_LEN: u64 = 4096;
+
+#[derive(Debug)]
+pub struct DupOptions {
+    pub min_size: u64,
+    pub json: bool,
+    /// Replace every duplicate with a hard link to the first of its group
+    pub hardlink: bool,
+}
+
+/// Files with the same contents
+#[derive(Debug, Serialize)]
+pub struct Group {
+    size: u64,
+    sha256: String,
+    paths: Vec<PathBuf>,
+}
+
+/// The files matched by the expression, collected during the walk
+#[derive(Debug, Default)]
+pub struct Candidates {
+    files: Vec<(PathBuf, u64)>,
+    /// Device and inode of every file so far, so that hard links to the
+    /// same file are only counted once
+    seen: HashSet<(u64, u64)>,
+}
+
+impl Candidates {
+    pub fn add(&mut self, entry: &Entry, opts: &DupOptions, ctx: &mut Context) {
+        if !entry.file_type().is_file() {
+            return;
+        }
+        match entry.metadata() {
+            Ok(meta) => {
+                if meta.len() >= opts.min_size && self.seen.insert((meta.dev(), meta.ino())) {
+                    self.files.push((entry.path().to_path_buf(), meta.len()));
+                }
+            }
+            Err(e) => ctx.error(format!("{}: {}", entry.path().display(), e)),
+        }
+    }
+
+    /// Narrow the files down to groups of duplicates: by size first, then
+    /// by a hash of their start, and only then by a hash of all of them
+    pub fn groups(self, ctx: &mut Context) -> Vec<Group> {
+        let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+        for (path, size) in self.files {
+            by_size.entry(size).or_default().push(path);
+        }
+
+        let mut groups = vec![];
+        for (size, paths) in by_size {
+            if paths.len() < 2 {
+                continue;
+            }
+            for (partial, paths) in split(paths, PARTIAL_LEN, ctx) {
+                let full = if size <= PARTIAL_LEN {
+                    vec![(partial, paths)]
+                } else {
+                    split(paths, u64::MAX, ctx)
+                };
+                for (sha256, mut paths) in full {
+                    paths.sort();
+                    groups.push(Group {
+                        size,
+                        sha256,
+                        paths,
+                    });
+                }
+            }
+        }
+        groups.sort_by(|a, b| a.paths.cmp(&b.paths));
+        groups
+    }
+}
+
+/// Group `paths` by the hash of up to `limit` bytes of them, keeping only
+/// the groups with more than one file
+fn split(paths: Vec<PathBuf>, limit: u64, ctx: &mut Context) -> Vec<(String, Vec<PathBuf>)> {
+    let mut groups: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for path in paths {
+        match hash(&path, limit) {
+            Ok(hash) => groups.entry(hash).or_default().push(path),
+            Err(e) => ctx.error(format!("{}: {}", path.display(), e)),
+        }
+    }
+    groups
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .collect()
+}
+
+fn hash(path: &Path, limit: u64) -> io::Result<String> {
+    let mut file = File::open(path)?.take(limit);
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Replace the other files of the group with hard links to the first one
+fn hardlink(group: &Group, ctx: &mut Context) {
+    let Some((keep, dups)) = group.paths.split_first() else {
+        return;
+    };
+    for dup in dups {
+        // Link under a new name and rename it over the duplicate, so it is
+        // never missing
+        let name = dup.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = dup.with_file_name(format!(".{}.findr-link", name));
+        let result = fs::hard_link(keep, &tmp).and_then(|_| {
+            fs::rename(&tmp, dup).inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })
+        });
+        if let Err(e) = result {
+            ctx.error(format!("{}: {}", dup.display(), e));
+        }
+    }
+}
+
+/// Print the groups, one path per line with a blank line between them,
+/// or as JSON
+pub fn report(groups: &[Group], opts: &DupOptions, ctx: &mut Context) -> MyResult<()> {
+    if opts.hardlink {
+        for group in groups {
+            hardlink(group, ctx);
+        }
+    }
+
+    if opts.json {
+        let json = serde_json::to_string_pretty(groups)?;
+        writeln!(ctx.out(), "{}", json)?;
+        return Ok(());
+    }
+    for (i, group) in groups.iter().enumerate() {
+        if i > 0 {
+            writeln!(ctx.out())?;
+        }
+        for path in &group.paths {
+            writeln!(ctx.out(), "{}", path.display())?;
+        }
+    }
+    Ok(())
+}
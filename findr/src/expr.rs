@@ -0,0 +1,864 @@
+use crate::action::{self, Action, Batch, Context};
+use crate::walk::Entry;
+use crate::EntryType::{self, *};
+use crate::MyResult;
+use glob::{MatchOptions, Pattern};
+use regex::{Regex, RegexBuilder};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A find-style expression, evaluated once per entry
+#[derive(Debug)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Test),
+    Action(Action),
+}
+
+#[derive(Debug)]
+pub enum Test {
+    True,
+    /// `-name` and `-iname`, a glob matched against the whole basename
+    Name(String, Pattern),
+    IName(String, Pattern),
+    /// `-path`, a glob matched against the whole path
+    Path(String, Pattern),
+    /// `-regex` and `-iregex`, matched against the whole path
+    Regex(String, Regex),
+    IRegex(String, Regex),
+    Type(Vec<EntryType>),
+    /// `-size`, with the size in bytes of one unit
+    Size(String, NumArg, u64),
+    /// `-atime`, `-mtime`, `-ctime` and `-mmin`, measured from `now`
+    Time {
+        field: TimeField,
+        minutes: bool,
+        arg: String,
+        cmp: NumArg,
+        now: SystemTime,
+    },
+    /// `-newer`, with the modification time of the reference file
+    Newer(String, SystemTime),
+    Perm(String, PermMode),
+    User(String, u32),
+    Group(String, u32),
+    Uid(String, NumArg),
+    Gid(String, NumArg),
+    Empty,
+}
+
+/// A number as find's predicates take them: `+N` for more than N, `-N` for
+/// less than N and plain `N` for exactly N
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumArg {
+    Less(u64),
+    Exactly(u64),
+    More(u64),
+}
+
+impl NumArg {
+    fn parse(val: &str) -> Option<NumArg> {
+        let (ctor, digits): (fn(u64) -> NumArg, _) = match val.strip_prefix('+') {
+            Some(rest) => (NumArg::More, rest),
+            None => match val.strip_prefix('-') {
+                Some(rest) => (NumArg::Less, rest),
+                None => (NumArg::Exactly, val),
+            },
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        digits.parse().ok().map(ctor)
+    }
+
+    fn matches(self, value: u64) -> bool {
+        match self {
+            NumArg::Less(n) => value < n,
+            NumArg::Exactly(n) => value == n,
+            NumArg::More(n) => value > n,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeField {
+    Access,
+    Modify,
+    Change,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermMode {
+    /// `-perm MODE`: exactly these permission bits
+    Exact(u32),
+    /// `-perm -MODE`: at least all of these bits
+    All(u32),
+    /// `-perm /MODE`: at least one of these bits
+    Any(u32),
+}
+
+impl Expr {
+    pub fn and(self, other: Expr) -> Expr {
+        match self {
+            Expr::Test(Test::True) => other,
+            _ => Expr::And(Box::new(self), Box::new(other)),
+        }
+    }
+
+    pub fn or(self, other: Expr) -> Expr {
+        Expr::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Evaluate the expression for `entry`, running its actions as they
+    /// are reached
+    pub fn eval(&mut self, entry: &Entry, ctx: &mut Context) -> io::Result<bool> {
+        Ok(match self {
+            Expr::And(left, right) => left.eval(entry, ctx)? && right.eval(entry, ctx)?,
+            Expr::Or(left, right) => left.eval(entry, ctx)? || right.eval(entry, ctx)?,
+            Expr::Not(expr) => !expr.eval(entry, ctx)?,
+            Expr::Test(test) => test.matches(entry),
+            Expr::Action(action) => action.apply(entry, ctx)?,
+        })
+    }
+
+    /// Run the actions that wait for the end of the walk
+    pub fn finish(&mut self, ctx: &mut Context) -> io::Result<()> {
+        match self {
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.finish(ctx)?;
+                right.finish(ctx)
+            }
+            Expr::Not(expr) => expr.finish(ctx),
+            Expr::Test(_) => Ok(()),
+            Expr::Action(action) => action.finish(ctx),
+        }
+    }
+
+    /// Whether any action in the expression satisfies `pred`
+    pub fn has_action(&self, pred: fn(&Action) -> bool) -> bool {
+        match self {
+            Expr::And(left, right) | Expr::Or(left, right) => {
+                left.has_action(pred) || right.has_action(pred)
+            }
+            Expr::Not(expr) => expr.has_action(pred),
+            Expr::Test(_) => false,
+            Expr::Action(action) => pred(action),
+        }
+    }
+}
+
+impl Test {
+    fn matches(&self, entry: &Entry) -> bool {
+        match self {
+            Test::True => true,
+            Test::Name(_, pattern) => {
+                pattern.matches_with(&entry.file_name().to_string_lossy(), glob_options(true))
+            }
+            Test::IName(_, pattern) => {
+                pattern.matches_with(&entry.file_name().to_string_lossy(), glob_options(false))
+            }
+            Test::Path(_, pattern) => {
+                pattern.matches_with(&entry.path().to_string_lossy(), glob_options(true))
+            }
+            Test::Regex(_, re) | Test::IRegex(_, re) => {
+                re.is_match(&entry.path().to_string_lossy())
+            }
+            Test::Type(entry_types) => entry_types.iter().any(|entry_type| match entry_type {
+                Link => entry.path_is_symlink(),
+                File => entry.file_type().is_file(),
+                Dir => entry.file_type().is_dir(),
+            }),
+            Test::Empty => {
+                if entry.file_type().is_dir() {
+                    fs::read_dir(entry.path())
+                        .map(|mut dir| dir.next().is_none())
+                        .unwrap_or(false)
+                } else {
+                    entry.file_type().is_file() && entry.metadata().is_ok_and(|m| m.len() == 0)
+                }
+            }
+            _ => match entry.metadata() {
+                Ok(meta) => self.matches_metadata(&meta),
+                Err(_) => false,
+            },
+        }
+    }
+
+    fn matches_metadata(&self, meta: &fs::Metadata) -> bool {
+        match self {
+            Test::Size(_, cmp, unit) => cmp.matches(meta.len().div_ceil(*unit)),
+            Test::Time {
+                field,
+                minutes,
+                cmp,
+                now,
+                ..
+            } => {
+                let (secs, nsecs) = match field {
+                    TimeField::Access => (meta.atime(), meta.atime_nsec()),
+                    TimeField::Modify => (meta.mtime(), meta.mtime_nsec()),
+                    TimeField::Change => (meta.ctime(), meta.ctime_nsec()),
+                };
+                let time = unix_time(secs, nsecs);
+                let age = now.duration_since(time).unwrap_or_default().as_secs();
+                cmp.matches(age / if *minutes { 60 } else { 24 * 60 * 60 })
+            }
+            Test::Newer(_, reference) => meta.modified().is_ok_and(|time| time > *reference),
+            Test::Perm(_, mode) => {
+                let bits = meta.mode() & 0o7777;
+                match *mode {
+                    PermMode::Exact(mode) => bits == mode,
+                    PermMode::All(mode) => bits & mode == mode,
+                    PermMode::Any(mode) => mode == 0 || bits & mode != 0,
+                }
+            }
+            Test::User(_, uid) => meta.uid() == *uid,
+            Test::Group(_, gid) => meta.gid() == *gid,
+            Test::Uid(_, cmp) => cmp.matches(meta.uid().into()),
+            Test::Gid(_, cmp) => cmp.matches(meta.gid().into()),
+            _ => false,
+        }
+    }
+}
+
+/// Match globs like find does, where `*` also matches a leading dot and `/`
+fn glob_options(case_sensitive: bool) -> MatchOptions {
+    MatchOptions {
+        case_sensitive,
+        require_literal_separator: false,
+        require_literal_leading_dot: false,
+    }
+}
+
+fn unix_time(secs: i64, nsecs: i64) -> SystemTime {
+    let nsecs = Duration::from_nanos(nsecs as u64);
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64) + nsecs
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + nsecs
+    }
+}
+
+/// Fully parenthesized, so the parse of an expression can be checked
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::And(left, right) => write!(f, "({} -a {})", left, right),
+            Expr::Or(left, right) => write!(f, "({} -o {})", left, right),
+            Expr::Not(expr) => write!(f, "! {}", expr),
+            Expr::Test(Test::True) => write!(f, "-true"),
+            Expr::Test(Test::Name(name, _)) => write!(f, "-name {}", name),
+            Expr::Test(Test::IName(name, _)) => write!(f, "-iname {}", name),
+            Expr::Test(Test::Path(path, _)) => write!(f, "-path {}", path),
+            Expr::Test(Test::Regex(regex, _)) => write!(f, "-regex {}", regex),
+            Expr::Test(Test::IRegex(regex, _)) => write!(f, "-iregex {}", regex),
+            Expr::Test(Test::Type(entry_types)) => {
+                let types: Vec<_> = entry_types
+                    .iter()
+                    .map(|entry_type| match entry_type {
+                        Dir => "d",
+                        File => "f",
+                        Link => "l",
+                    })
+                    .collect();
+                write!(f, "-type {}", types.join(","))
+            }
+            Expr::Test(Test::Size(arg, ..)) => write!(f, "-size {}", arg),
+            Expr::Test(Test::Time {
+                field,
+                minutes,
+                arg,
+                ..
+            }) => {
+                let field = match field {
+                    TimeField::Access => 'a',
+                    TimeField::Modify => 'm',
+                    TimeField::Change => 'c',
+                };
+                let unit = if *minutes { "min" } else { "time" };
+                write!(f, "-{}{} {}", field, unit, arg)
+            }
+            Expr::Test(Test::Newer(file, _)) => write!(f, "-newer {}", file),
+            Expr::Test(Test::Perm(mode, _)) => write!(f, "-perm {}", mode),
+            Expr::Test(Test::User(user, _)) => write!(f, "-user {}", user),
+            Expr::Test(Test::Group(group, _)) => write!(f, "-group {}", group),
+            Expr::Test(Test::Uid(uid, _)) => write!(f, "-uid {}", uid),
+            Expr::Test(Test::Gid(gid, _)) => write!(f, "-gid {}", gid),
+            Expr::Test(Test::Empty) => write!(f, "-empty"),
+            Expr::Action(action) => write!(f, "{}", action),
+        }
+    }
+}
+
+/// Options that appear in the expression but apply to the whole walk
+#[derive(Debug, Default)]
+pub struct Options {
+    /// `-xdev`: stay on the file system of each starting point
+    pub same_file_system: bool,
+    pub max_depth: Option<usize>,
+    pub min_depth: Option<usize>,
+    /// `-depth`: visit the contents of a directory before the directory
+    pub contents_first: bool,
+}
+
+/// Whether `arg` begins the expression, so it and everything after it
+/// belong to the expression rather than to the list of paths. A short
+/// option with its number attached, like `-j4`, is not a predicate
+pub fn is_expression_start(arg: &str) -> bool {
+    let short_with_number = arg.len() > 2 && arg[2..].bytes().all(|b| b.is_ascii_digit());
+    matches!(arg, "!" | "(")
+        || (arg.starts_with('-') && !arg.starts_with("--") && arg.len() > 2 && !short_with_number)
+}
+
+/// Parse the expression part of the command line. Operators bind as in
+/// find: `!` tightest, then `-a` (or two expressions side by side), then
+/// `-o`, with parentheses for grouping
+pub fn parse(args: &[String]) -> MyResult<(Expr, Options)> {
+    let mut parser = Parser {
+        args,
+        pos: 0,
+        options: Options::default(),
+    };
+    if args.is_empty() {
+        return Ok((Expr::Test(Test::True), parser.options));
+    }
+    let expr = parser.parse_or()?;
+    match parser.peek() {
+        None => Ok((expr, parser.options)),
+        Some(")") => Err(From::from("Unexpected \")\"")),
+        Some(arg) => Err(From::from(format!(
+            "Unexpected \"{}\", paths must precede the expression",
+            arg
+        ))),
+    }
+}
+
+struct Parser<'a> {
+    args: &'a [String],
+    pos: usize,
+    options: Options,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&str> {
+        self.args.get(self.pos).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<&str> {
+        let arg = self.args.get(self.pos).map(String::as_str);
+        self.pos += 1;
+        arg
+    }
+
+    fn parse_or(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_and()?;
+        while let Some("-o" | "-or") = self.peek() {
+            self.next();
+            self.expect_operand("-o")?;
+            expr = expr.or(self.parse_and()?);
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> MyResult<Expr> {
+        let mut expr = self.parse_not()?;
+        loop {
+            match self.peek() {
+                Some("-a" | "-and") => {
+                    self.next();
+                    self.expect_operand("-a")?;
+                    let right = self.parse_not()?;
+                    expr = Expr::And(Box::new(expr), Box::new(right));
+                }
+                None | Some("-o" | "-or" | ")") => return Ok(expr),
+                Some(_) => {
+                    let right = self.parse_not()?;
+                    expr = Expr::And(Box::new(expr), Box::new(right));
+                }
+            }
+        }
+    }
+
+    /// Check that something follows `operator` for it to apply to
+    fn expect_operand(&self, operator: &str) -> MyResult<()> {
+        match self.peek() {
+            None | Some("-o" | "-or" | "-a" | "-and" | ")") => Err(From::from(format!(
+                "Expected an expression after {}",
+                operator
+            ))),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn parse_not(&mut self) -> MyResult<Expr> {
+        match self.peek() {
+            Some("!" | "-not") => {
+                let operator = self.next().unwrap_or_default().to_string();
+                self.expect_operand(&operator)?;
+                Ok(Expr::Not(Box::new(self.parse_not()?)))
+            }
+            _ => self.parse_primary(),
+        }
+    }
+
+    fn parse_primary(&mut self) -> MyResult<Expr> {
+        let arg = match self.next() {
+            Some(arg) => arg.to_string(),
+            None => return Err(From::from("Expected an expression")),
+        };
+
+        match arg.as_str() {
+            "(" => {
+                if let Some(")") = self.peek() {
+                    return Err(From::from("Empty parentheses are not allowed"));
+                }
+                let expr = self.parse_or()?;
+                match self.next() {
+                    Some(")") => Ok(expr),
+                    _ => Err(From::from("Missing closing \")\"")),
+                }
+            }
+            "-name" | "-iname" => {
+                let name = self.value(&arg)?;
+                let pattern =
+                    Pattern::new(&name).map_err(|_| format!("Invalid {} \"{}\"", arg, name))?;
+                Ok(Expr::Test(if arg == "-iname" {
+                    Test::IName(name, pattern)
+                } else {
+                    Test::Name(name, pattern)
+                }))
+            }
+            "-path" => {
+                let path = self.value(&arg)?;
+                let pattern =
+                    Pattern::new(&path).map_err(|_| format!("Invalid -path \"{}\"", path))?;
+                Ok(Expr::Test(Test::Path(path, pattern)))
+            }
+            "-regex" | "-iregex" => {
+                let regex = self.value(&arg)?;
+                // Like find, the pattern has to match the whole path
+                let re = RegexBuilder::new(&format!("^(?:{})$", regex))
+                    .case_insensitive(arg == "-iregex")
+                    .build()
+                    .map_err(|_| format!("Invalid {} \"{}\"", arg, regex))?;
+                Ok(Expr::Test(if arg == "-iregex" {
+                    Test::IRegex(regex, re)
+                } else {
+                    Test::Regex(regex, re)
+                }))
+            }
+            "-type" => {
+                let types = self.value(&arg)?;
+                let entry_types = types
+                    .split(',')
+                    .map(|t| match t {
+                        "f" => Ok(File),
+                        "d" => Ok(Dir),
+                        "l" => Ok(Link),
+                        _ => Err(format!("Invalid -type \"{}\"", t)),
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Expr::Test(Test::Type(entry_types)))
+            }
+            "-size" => {
+                let size = self.value(&arg)?;
+                let (cmp, unit) =
+                    parse_size(&size).ok_or_else(|| format!("Invalid -size \"{}\"", size))?;
+                Ok(Expr::Test(Test::Size(size, cmp, unit)))
+            }
+            "-atime" | "-mtime" | "-ctime" | "-mmin" => {
+                let val = self.value(&arg)?;
+                let cmp =
+                    NumArg::parse(&val).ok_or_else(|| format!("Invalid {} \"{}\"", arg, val))?;
+                let field = match &arg[1..2] {
+                    "a" => TimeField::Access,
+                    "c" => TimeField::Change,
+                    _ => TimeField::Modify,
+                };
+                Ok(Expr::Test(Test::Time {
+                    field,
+                    minutes: arg.ends_with("min"),
+                    arg: val,
+                    cmp,
+                    now: SystemTime::now(),
+                }))
+            }
+            "-newer" => {
+                let file = self.value(&arg)?;
+                let modified = fs::metadata(&file)
+                    .and_then(|meta| meta.modified())
+                    .map_err(|e| format!("{}: {}", file, e))?;
+                Ok(Expr::Test(Test::Newer(file, modified)))
+            }
+            "-perm" => {
+                let mode = self.value(&arg)?;
+                let perm =
+                    parse_perm(&mode).ok_or_else(|| format!("Invalid -perm \"{}\"", mode))?;
+                Ok(Expr::Test(Test::Perm(mode, perm)))
+            }
+            "-user" => {
+                let user = self.value(&arg)?;
+                let uid = users::get_user_by_name(&user)
+                    .map(|u| u.uid())
+                    .or_else(|| user.parse().ok())
+                    .ok_or_else(|| format!("Unknown user \"{}\"", user))?;
+                Ok(Expr::Test(Test::User(user, uid)))
+            }
+            "-group" => {
+                let group = self.value(&arg)?;
+                let gid = users::get_group_by_name(&group)
+                    .map(|g| g.gid())
+                    .or_else(|| group.parse().ok())
+                    .ok_or_else(|| format!("Unknown group \"{}\"", group))?;
+                Ok(Expr::Test(Test::Group(group, gid)))
+            }
+            "-uid" | "-gid" => {
+                let id = self.value(&arg)?;
+                let cmp =
+                    NumArg::parse(&id).ok_or_else(|| format!("Invalid {} \"{}\"", arg, id))?;
+                Ok(Expr::Test(if arg == "-uid" {
+                    Test::Uid(id, cmp)
+                } else {
+                    Test::Gid(id, cmp)
+                }))
+            }
+            "-empty" => Ok(Expr::Test(Test::Empty)),
+            "-print" => Ok(Expr::Action(Action::Print)),
+            "-print0" => Ok(Expr::Action(Action::Print0)),
+            "-printf" => {
+                let format = self.value(&arg)?;
+                let directives = action::parse_printf(&format)?;
+                Ok(Expr::Action(Action::Printf(format, directives)))
+            }
+            "-delete" => Ok(Expr::Action(Action::Delete)),
+            "-prune" => Ok(Expr::Action(Action::Prune)),
+            // Like find, options are true wherever they appear
+            "-xdev" | "-mount" => {
+                self.options.same_file_system = true;
+                Ok(Expr::Test(Test::True))
+            }
+            "-maxdepth" | "-mindepth" => {
+                let val = self.value(&arg)?;
+                let depth = parse_depth(&arg, &val)?;
+                if arg == "-maxdepth" {
+                    self.options.max_depth = Some(depth);
+                } else {
+                    self.options.min_depth = Some(depth);
+                }
+                Ok(Expr::Test(Test::True))
+            }
+            "-depth" => {
+                self.options.contents_first = true;
+                Ok(Expr::Test(Test::True))
+            }
+            "-exec" => self.parse_exec(),
+            "-a" | "-and" | "-o" | "-or" => {
+                Err(From::from(format!("Expected an expression before {}", arg)))
+            }
+            _ if !arg.starts_with('-') => Err(From::from(format!(
+                "Unexpected \"{}\", paths must precede the expression",
+                arg
+            ))),
+            _ => Err(From::from(format!("Unknown predicate \"{}\"", arg))),
+        }
+    }
+
+    /// The command of `-exec`, which runs up to `;`, or up to `{} +` to
+    /// pass many paths at once
+    fn parse_exec(&mut self) -> MyResult<Expr> {
+        let mut command: Vec<String> = vec![];
+        let batched = loop {
+            match self.next() {
+                Some(";") => break false,
+                Some("+") if command.last().map(String::as_str) == Some("{}") => {
+                    command.pop();
+                    break true;
+                }
+                Some(arg) => command.push(arg.to_string()),
+                None => return Err(From::from("Missing argument to -exec")),
+            }
+        };
+        if command.is_empty() {
+            return Err(From::from("Missing argument to -exec"));
+        }
+        if !batched {
+            return Ok(Expr::Action(Action::Exec(command)));
+        }
+        if command.iter().any(|arg| arg.contains("{}")) {
+            return Err(From::from("Only one {} is supported with -exec ... +"));
+        }
+        Ok(Expr::Action(Action::ExecBatch(Batch::new(command))))
+    }
+
+    /// The argument that `predicate` takes
+    fn value(&mut self, predicate: &str) -> MyResult<String> {
+        self.next()
+            .map(str::to_string)
+            .ok_or_else(|| From::from(format!("Missing argument to {}", predicate)))
+    }
+}
+
+/// Parse the value of a depth option, named `option` in the error
+pub fn parse_depth(option: &str, val: &str) -> MyResult<usize> {
+    val.parse()
+        .map_err(|_| From::from(format!("Invalid {} \"{}\"", option, val)))
+}
+
+/// Parse a `-size` argument such as `+10k` into the comparison and the
+/// size of its unit: c for bytes, k, M and G for KiB, MiB and GiB, and
+/// 512-byte blocks when there is no suffix
+fn parse_size(val: &str) -> Option<(NumArg, u64)> {
+    let (num, unit) = match val.char_indices().last()? {
+        (i, 'c') => (&val[..i], 1),
+        (i, 'w') => (&val[..i], 2),
+        (i, 'b') => (&val[..i], 512),
+        (i, 'k') => (&val[..i], 1024),
+        (i, 'M') => (&val[..i], 1024 * 1024),
+        (i, 'G') => (&val[..i], 1024 * 1024 * 1024),
+        _ => (val, 512),
+    };
+    NumArg::parse(num).map(|cmp| (cmp, unit))
+}
+
+/// Parse an octal `-perm` mode, optionally prefixed with `-` or `/`
+fn parse_perm(val: &str) -> Option<PermMode> {
+    let (ctor, digits): (fn(u32) -> PermMode, _) = match val.strip_prefix('-') {
+        Some(rest) => (PermMode::All, rest),
+        None => match val.strip_prefix('/') {
+            Some(rest) => (PermMode::Any, rest),
+            None => (PermMode::Exact, val),
+        },
+    };
+    if digits.is_empty() || digits.starts_with('+') {
+        return None;
+    }
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .map(ctor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_expression_start, parse, parse_perm, parse_size, NumArg, PermMode};
+
+    fn parsed(args: &str) -> String {
+        let args: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+        parse(&args).unwrap().0.to_string()
+    }
+
+    fn parse_error(args: &str) -> String {
+        let args: Vec<String> = args.split_whitespace().map(str::to_string).collect();
+        parse(&args).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_parse_single() {
+        assert_eq!(parsed(""), "-true");
+        assert_eq!(parsed("-name a"), "-name a");
+        assert_eq!(parsed("-iname A"), "-iname A");
+        assert_eq!(parsed("-type f,l"), "-type f,l");
+    }
+
+    #[test]
+    fn test_and_binds_tighter_than_or() {
+        assert_eq!(
+            parsed("-name a -o -name b -a -type f"),
+            "(-name a -o (-name b -a -type f))"
+        );
+        assert_eq!(
+            parsed("-name a -a -name b -o -type f"),
+            "((-name a -a -name b) -o -type f)"
+        );
+    }
+
+    #[test]
+    fn test_implicit_and() {
+        assert_eq!(parsed("-name a -type f"), "(-name a -a -type f)");
+        assert_eq!(
+            parsed("-name a -type f -o -type d"),
+            "((-name a -a -type f) -o -type d)"
+        );
+    }
+
+    #[test]
+    fn test_not_binds_tightest() {
+        assert_eq!(parsed("! -name a -type f"), "(! -name a -a -type f)");
+        assert_eq!(parsed("-not -name a -o -type d"), "(! -name a -o -type d)");
+        assert_eq!(parsed("! ! -type d"), "! ! -type d");
+    }
+
+    #[test]
+    fn test_parentheses() {
+        assert_eq!(
+            parsed("( -name a -o -name b ) -type f"),
+            "((-name a -o -name b) -a -type f)"
+        );
+        assert_eq!(parsed("! ( -name a -o -name b )"), "! (-name a -o -name b)");
+        assert_eq!(parsed("( ( -type d ) )"), "-type d");
+    }
+
+    #[test]
+    fn test_left_associative() {
+        assert_eq!(
+            parsed("-name a -o -name b -o -name c"),
+            "((-name a -o -name b) -o -name c)"
+        );
+        assert_eq!(
+            parsed("-name a -name b -name c"),
+            "((-name a -a -name b) -a -name c)"
+        );
+    }
+
+    #[test]
+    fn test_num_arg() {
+        assert_eq!(NumArg::parse("3"), Some(NumArg::Exactly(3)));
+        assert_eq!(NumArg::parse("+3"), Some(NumArg::More(3)));
+        assert_eq!(NumArg::parse("-3"), Some(NumArg::Less(3)));
+        assert_eq!(NumArg::parse(""), None);
+        assert_eq!(NumArg::parse("+"), None);
+        assert_eq!(NumArg::parse("+-3"), None);
+        assert_eq!(NumArg::parse("3d"), None);
+
+        assert!(NumArg::More(3).matches(4));
+        assert!(!NumArg::More(3).matches(3));
+        assert!(NumArg::Less(3).matches(2));
+        assert!(!NumArg::Less(3).matches(3));
+        assert!(NumArg::Exactly(3).matches(3));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("10"), Some((NumArg::Exactly(10), 512)));
+        assert_eq!(parse_size("+10c"), Some((NumArg::More(10), 1)));
+        assert_eq!(parse_size("-2k"), Some((NumArg::Less(2), 1024)));
+        assert_eq!(parse_size("1M"), Some((NumArg::Exactly(1), 1024 * 1024)));
+        assert_eq!(parse_size("+1G"), Some((NumArg::More(1), 1 << 30)));
+        assert_eq!(parse_size("k"), None);
+        assert_eq!(parse_size("10x"), None);
+        assert_eq!(parse_size(""), None);
+    }
+
+    #[test]
+    fn test_parse_perm() {
+        assert_eq!(parse_perm("644"), Some(PermMode::Exact(0o644)));
+        assert_eq!(parse_perm("-111"), Some(PermMode::All(0o111)));
+        assert_eq!(parse_perm("/022"), Some(PermMode::Any(0o022)));
+        assert_eq!(parse_perm("4755"), Some(PermMode::Exact(0o4755)));
+        assert_eq!(parse_perm("9"), None);
+        assert_eq!(parse_perm("17777"), None);
+        assert_eq!(parse_perm("u+x"), None);
+        assert_eq!(parse_perm("/"), None);
+    }
+
+    #[test]
+    fn test_parse_metadata_tests() {
+        assert_eq!(
+            parsed("-size +1k -mtime -2 -o -mmin 30 -empty"),
+            "((-size +1k -a -mtime -2) -o (-mmin 30 -a -empty))"
+        );
+        assert_eq!(
+            parsed("-perm /111 ! -uid 0 -gid +10"),
+            "((-perm /111 -a ! -uid 0) -a -gid +10)"
+        );
+        assert_eq!(parsed("-user 0 -o -group 0"), "(-user 0 -o -group 0)");
+    }
+
+    #[test]
+    fn test_parse_actions() {
+        assert_eq!(parsed("-print"), "-print");
+        assert_eq!(
+            parsed("-name a -print0 -o -delete"),
+            "((-name a -a -print0) -o -delete)"
+        );
+        assert_eq!(parsed("-printf %p\\n"), "-printf %p\\n");
+        assert_eq!(
+            parsed("-exec rm -f {} ; -exec echo a{}b ; -o -exec ls -l {} +"),
+            "((-exec rm -f {} ; -a -exec echo a{}b ;) -o -exec ls -l {} +)"
+        );
+        assert_eq!(parsed("-exec echo + ;"), "-exec echo + ;");
+        assert_eq!(
+            parsed("-name .git -prune -o -print"),
+            "((-name .git -a -prune) -o -print)"
+        );
+        assert_eq!(parsed("-exec echo ( ) ;"), "-exec echo ( ) ;");
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args =
+            |args: &str| -> Vec<String> { args.split_whitespace().map(str::to_string).collect() };
+        assert!(!parse(&args("-name a")).unwrap().1.same_file_system);
+        let (expr, options) = parse(&args("-xdev -name a")).unwrap();
+        assert!(options.same_file_system);
+        assert_eq!(expr.to_string(), "(-true -a -name a)");
+        assert!(parse(&args("-mount")).unwrap().1.same_file_system);
+
+        let (_, options) = parse(&args("-maxdepth 2 -mindepth 1 -depth -name a")).unwrap();
+        assert_eq!(options.max_depth, Some(2));
+        assert_eq!(options.min_depth, Some(1));
+        assert!(options.contents_first);
+        let (_, options) = parse(&args("-name a")).unwrap();
+        assert_eq!(options.max_depth, None);
+        assert_eq!(options.min_depth, None);
+        assert!(!options.contents_first);
+    }
+
+    #[test]
+    fn test_is_expression_start() {
+        for arg in ["!", "(", "-name", "-print0", "-xdev"] {
+            assert!(is_expression_start(arg), "{}", arg);
+        }
+        for arg in ["tests", "-L", "-j", "-j4", "--sort", "--max_depth", ")"] {
+            assert!(!is_expression_start(arg), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(parse_error("-name"), "Missing argument to -name");
+        assert_eq!(parse_error("-name a -o"), "Expected an expression after -o");
+        assert_eq!(
+            parse_error("-a -name a"),
+            "Expected an expression before -a"
+        );
+        assert_eq!(parse_error("!"), "Expected an expression after !");
+        assert_eq!(parse_error("( -name a"), "Missing closing \")\"");
+        assert_eq!(parse_error("-name a )"), "Unexpected \")\"");
+        assert_eq!(parse_error("( )"), "Empty parentheses are not allowed");
+        assert_eq!(parse_error("-size 1x"), "Invalid -size \"1x\"");
+        assert_eq!(parse_error("-mtime x"), "Invalid -mtime \"x\"");
+        assert_eq!(parse_error("-perm 999"), "Invalid -perm \"999\"");
+        assert_eq!(parse_error("-uid"), "Missing argument to -uid");
+        assert_eq!(parse_error("-maxdepth"), "Missing argument to -maxdepth");
+        assert_eq!(parse_error("-maxdepth -1"), "Invalid -maxdepth \"-1\"");
+        assert_eq!(parse_error("-mindepth x"), "Invalid -mindepth \"x\"");
+        assert_eq!(parse_error("-exec rm {}"), "Missing argument to -exec");
+        assert_eq!(parse_error("-exec ;"), "Missing argument to -exec");
+        assert_eq!(parse_error("-exec echo +"), "Missing argument to -exec");
+        assert_eq!(
+            parse_error("-exec mv {} {} +"),
+            "Only one {} is supported with -exec ... +"
+        );
+        assert_eq!(
+            parse_error("-printf %z"),
+            "Invalid -printf directive \"%z\""
+        );
+        assert_eq!(
+            parse_error("-user no-such-user-here"),
+            "Unknown user \"no-such-user-here\""
+        );
+        assert_eq!(parse_error("-type x"), "Invalid -type \"x\"");
+        assert_eq!(
+            parse_error("-name a tests"),
+            "Unexpected \"tests\", paths must precede the expression"
+        );
+        assert_eq!(parse_error("-name [a"), "Invalid -name \"[a\"");
+        assert_eq!(parse_error("-regex ("), "Invalid -regex \"(\"");
+    }
+}
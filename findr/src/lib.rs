@@ -1,8 +1,17 @@
+mod action;
+mod dupes;
+mod expr;
+mod walk;
+
 use crate::EntryType::*;
+use action::{Action, Context};
 use clap::{App, Arg};
-use regex::Regex;
+use dupes::{Candidates, DupOptions};
+use expr::{Expr, Test};
+use glob::Pattern;
 use std::error::Error;
-use walkdir::{DirEntry, WalkDir};
+use std::io::{self, BufWriter, IsTerminal, Write};
+use walk::WalkOptions;
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -16,13 +25,22 @@ enum EntryType {
 #[derive(Debug)]
 pub struct Config {
     paths: Vec<String>,
-    names: Vec<Regex>,
-    entry_types: Vec<EntryType>,
-    max_depth: usize,
-    min_depth: usize,
+    expr: Expr,
+    walk: WalkOptions,
+    duplicates: Option<DupOptions>,
 }
 
 pub fn get_args() -> MyResult<Config> {
+    // As with find, the expression starts at the first predicate, operator
+    // or parenthesis, and everything before it is an option or a path
+    let args: Vec<String> = std::env::args().collect();
+    let expr_start = args
+        .iter()
+        .skip(1)
+        .position(|arg| expr::is_expression_start(arg))
+        .map_or(args.len(), |i| i + 1);
+    let (args, expr_args) = args.split_at(expr_start);
+
     let matches = App::new("findr")
         .version("0.0.1")
         .author("Ken Youens-Clark <kyclark@gmail.com>")
@@ -34,12 +52,30 @@ pub fn get_args() -> MyResult<Config> {
                 .default_value(".")
                 .multiple(true),
         )
+        .arg(
+            Arg::with_name("follow")
+                .short("L")
+                .help("Follow symbolic links"),
+        )
+        .arg(
+            Arg::with_name("jobs")
+                .value_name("JOBS")
+                .short("j")
+                .long("jobs")
+                .help("Read directories on JOBS threads")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .help("Visit the entries of each directory sorted by name"),
+        )
         .arg(
             Arg::with_name("name")
                 .value_name("NAME")
                 .short("n")
                 .long("name")
-                .help("Name")
+                .help("Name glob")
                 .takes_value(true)
                 .multiple(true),
         )
@@ -57,38 +93,55 @@ pub fn get_args() -> MyResult<Config> {
             Arg::with_name("max_depth")
                 .value_name("MAX DEPTH")
                 .long("max_depth")
-                .default_value("10")
+                .help("Same as -maxdepth")
                 .takes_value(true),
         )
         .arg(
             Arg::with_name("min_depth")
                 .value_name("MIN DEPTH")
                 .long("min_depth")
-                .default_value("0")
+                .help("Same as -mindepth")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("duplicates")
+                .long("duplicates")
+                .help("Print groups of matching files with the same contents"),
+        )
+        .arg(
+            Arg::with_name("min_size")
+                .value_name("BYTES")
+                .long("min_size")
+                .help("Only compare files of at least BYTES [default: 1]")
+                .requires("duplicates")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hardlink")
+                .long("hardlink")
+                .help("Replace duplicates with hard links to the first file of their group")
+                .requires("duplicates"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .value_name("FORMAT")
+                .long("format")
+                .help("Output format of the duplicates")
+                .possible_values(&["text", "json"])
+                .requires("duplicates")
                 .takes_value(true),
         )
-        .get_matches();
+        .get_matches_from(args);
 
     let paths: Vec<String> = matches.values_of_lossy("path").unwrap();
-    /*
-    let names: Vec<Regex> = matches
-        .values_of_lossy("name")
-        .map(|vals| {
-            vals.into_iter()
-                .map(|name| Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name)))
-                .collect::<Result<Vec<_>, _>>()
-        })
-        .transpose()?
-        .unwrap_or_default();
-    */
-    let names: Vec<Regex> = matches
+    let names: Vec<Pattern> = matches
         .values_of_lossy("name")
         .unwrap_or(vec![])
         .into_iter()
-        .map(|name| Regex::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name)))
+        .map(|name| Pattern::new(&name).map_err(|_| format!("Invalid --name \"{}\"", name)))
         .collect::<Result<Vec<_>, _>>()?;
 
-    let entry_types = matches
+    let entry_types: Vec<EntryType> = matches
         .values_of_lossy("type")
         .unwrap_or(vec![])
         .iter()
@@ -100,68 +153,133 @@ pub fn get_args() -> MyResult<Config> {
         })
         .collect();
 
-    let max_depth = matches
-        .value_of("max_depth")
-        .unwrap()
-        .to_owned()
-        .parse::<usize>()
-        .unwrap();
-    let min_depth = matches
-        .value_of("min_depth")
-        .unwrap()
-        .to_owned()
-        .parse::<usize>()
-        .unwrap();
+    // --name and --type are shorthands ANDed with the expression
+    let mut expr = Expr::Test(Test::True);
+    if let Some(any_name) = names
+        .into_iter()
+        .map(|pattern| Expr::Test(Test::Name(pattern.to_string(), pattern)))
+        .reduce(Expr::or)
+    {
+        expr = expr.and(any_name);
+    }
+    if !entry_types.is_empty() {
+        expr = expr.and(Expr::Test(Test::Type(entry_types)));
+    }
+    let (parsed, options) = expr::parse(expr_args)?;
+    let mut expr = expr.and(parsed);
+    let duplicates = matches.is_present("duplicates");
+    if duplicates {
+        // The expression only picks the files to compare
+        if expr.has_action(|a| !matches!(a, Action::Prune)) {
+            return Err(From::from("Actions cannot be used with --duplicates"));
+        }
+    } else if !expr.has_action(|a| !matches!(a, Action::Prune)) {
+        // Without any action other than -prune, matching entries are printed
+        expr = expr.and(Expr::Action(Action::Print));
+    }
+
+    let follow_links = matches.is_present("follow");
+    if follow_links && expr.has_action(|a| matches!(a, Action::Delete)) {
+        return Err(From::from("-delete cannot be used with -L"));
+    }
+
+    // -maxdepth and -mindepth in the expression win over the long options,
+    // and there is no limit unless one is given
+    let max_depth = match (options.max_depth, matches.value_of("max_depth")) {
+        (Some(depth), _) => depth,
+        (None, Some(val)) => expr::parse_depth("--max_depth", val)?,
+        (None, None) => usize::MAX,
+    };
+    let min_depth = match (options.min_depth, matches.value_of("min_depth")) {
+        (Some(depth), _) => depth,
+        (None, Some(val)) => expr::parse_depth("--min_depth", val)?,
+        (None, None) => 0,
+    };
+
+    // -delete can only remove a directory once its contents are gone
+    let contents_first = options.contents_first || expr.has_action(|a| matches!(a, Action::Delete));
+
+    let threads = matches
+        .value_of("jobs")
+        .map(|val| match val.parse() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("Invalid --jobs \"{}\"", val)),
+        })
+        .transpose()?
+        .unwrap_or(1);
+
+    let duplicates = if duplicates {
+        let min_size = matches
+            .value_of("min_size")
+            .map(|val| {
+                val.parse()
+                    .map_err(|_| format!("Invalid --min_size \"{}\"", val))
+            })
+            .transpose()?
+            .unwrap_or(1);
+        Some(DupOptions {
+            min_size,
+            json: matches.value_of("format") == Some("json"),
+            hardlink: matches.is_present("hardlink"),
+        })
+    } else {
+        None
+    };
 
     Ok(Config {
         paths,
-        names,
-        entry_types,
-        max_depth,
-        min_depth,
+        expr,
+        walk: WalkOptions {
+            max_depth,
+            min_depth,
+            contents_first,
+            follow_links,
+            same_file_system: options.same_file_system,
+            sort: matches.is_present("sort"),
+            threads,
+        },
+        duplicates,
     })
 }
 
-pub fn run(config: Config) -> MyResult<()> {
-    println!("{:?}", config);
-    let type_filter = |entry: &DirEntry| {
-        config.entry_types.is_empty()
-            || config
-                .entry_types
-                .iter()
-                .any(|entry_type| match entry_type {
-                    Link => entry.path_is_symlink(),
-                    File => entry.file_type().is_file(),
-                    Dir => entry.file_type().is_dir(),
-                })
-    };
-
-    let name_filter = |entry: &DirEntry| {
-        config.names.is_empty()
-            || config
-                .names
-                .iter()
-                .any(|name| name.is_match(&entry.file_name().to_string_lossy()))
+/// Walk the paths and evaluate the expression for every entry, returning
+/// whether every action succeeded
+pub fn run(mut config: Config) -> MyResult<bool> {
+    // Matches show up as they are found on a terminal, and are written in
+    // blocks otherwise
+    let out: Box<dyn Write> = if io::stdout().is_terminal() {
+        Box::new(io::stdout())
+    } else {
+        Box::new(BufWriter::new(io::stdout()))
     };
-
-    for path in &config.paths {
-        let entries = WalkDir::new(path)
-            .min_depth(config.min_depth) // follow WalkDir's default min depth value
-            .max_depth(config.max_depth) // follow WalkDir's default max depth value
-            .into_iter()
-            .filter_map(|e| match e {
-                Ok(entry) => Some(entry),
-                Err(e) => {
-                    eprintln!("{}", e);
-                    None
+    let mut ctx = Context::new(out);
+    let mut candidates = Candidates::default();
+    // Both walkers go through the same evaluation, in the same order
+    let mut visit = |entry: Result<&walk::Entry, String>| -> MyResult<bool> {
+        match entry {
+            Ok(entry) => {
+                let matched = config.expr.eval(entry, &mut ctx)?;
+                if let (true, Some(opts)) = (matched, &config.duplicates) {
+                    candidates.add(entry, opts, &mut ctx);
                 }
-            })
-            .filter(type_filter)
-            .filter(name_filter)
-            .map(|entry| entry.path().display().to_string())
-            .collect::<Vec<_>>();
-
-        println!("{}", entries.join("\n"));
+                Ok(ctx.take_prune())
+            }
+            Err(e) => {
+                ctx.error(e);
+                Ok(false)
+            }
+        }
+    };
+    if config.walk.threads > 1 {
+        walk::parallel(&config.paths, &config.walk, &mut visit)?;
+    } else {
+        walk::serial(&config.paths, &config.walk, &mut visit)?;
+    }
+    config.expr.finish(&mut ctx)?;
+    if let Some(opts) = &config.duplicates {
+        let groups = candidates.groups(&mut ctx);
+        dupes::report(&groups, opts, &mut ctx)?;
     }
-    Ok(())
+    ctx.flush()?;
+    Ok(!ctx.failed())
 }
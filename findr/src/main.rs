@@ -1,6 +1,10 @@
 fn main() {
-    if let Err(e) = findr::get_args().and_then(findr::run) {
-        eprintln!("{}", e);
-        std::process::exit(1);
+    match findr::get_args().and_then(findr::run) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
     }
 }
@@ -0,0 +1,383 @@
+use crate::MyResult;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::fs::{self, FileType, Metadata};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use walkdir::WalkDir;
+
+/// Called for every entry or error, in walk order. Returns whether
+/// `-prune` asked to skip the contents of the entry
+pub type Visit<'a> = dyn FnMut(Result<&Entry, String>) -> MyResult<bool> + 'a;
+
+#[derive(Debug)]
+pub struct WalkOptions {
+    pub max_depth: usize,
+    pub min_depth: usize,
+    pub contents_first: bool,
+    pub follow_links: bool,
+    pub same_file_system: bool,
+    /// Visit the entries of each directory sorted by name
+    pub sort: bool,
+    /// More than one reads directories on that many threads
+    pub threads: usize,
+}
+
+/// A file found by either walker, which is all the expression looks at
+#[derive(Debug)]
+pub struct Entry {
+    path: PathBuf,
+    depth: usize,
+    file_type: FileType,
+    path_is_symlink: bool,
+}
+
+impl Entry {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn file_name(&self) -> &OsStr {
+        self.path.file_name().unwrap_or(self.path.as_os_str())
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// The type of the entry, or of its target when a link is followed
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn path_is_symlink(&self) -> bool {
+        self.path_is_symlink
+    }
+
+    /// The metadata of the entry, or of its target when a link is followed
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        if self.file_type.is_symlink() {
+            fs::symlink_metadata(&self.path)
+        } else {
+            fs::metadata(&self.path)
+        }
+    }
+}
+
+impl From<walkdir::DirEntry> for Entry {
+    fn from(entry: walkdir::DirEntry) -> Self {
+        Entry {
+            depth: entry.depth(),
+            file_type: entry.file_type(),
+            path_is_symlink: entry.path_is_symlink(),
+            path: entry.into_path(),
+        }
+    }
+}
+
+/// Walk the paths one directory at a time
+pub fn serial(paths: &[String], opts: &WalkOptions, visit: &mut Visit) -> MyResult<()> {
+    for path in paths {
+        let mut walker = WalkDir::new(path)
+            .min_depth(opts.min_depth)
+            .max_depth(opts.max_depth)
+            .contents_first(opts.contents_first)
+            .follow_links(opts.follow_links)
+            .same_file_system(opts.same_file_system);
+        if opts.sort {
+            walker = walker.sort_by_file_name();
+        }
+        let mut entries = walker.into_iter();
+        while let Some(entry) = entries.next() {
+            match entry {
+                Ok(entry) => {
+                    let entry = Entry::from(entry);
+                    // Too late to prune once the contents have been visited
+                    if visit(Ok(&entry))? && entry.file_type().is_dir() && !opts.contents_first {
+                        entries.skip_current_dir();
+                    }
+                }
+                Err(e) => {
+                    visit(Err(e.to_string()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The entries of a directory, or why it could not be read
+type Listing = Result<Vec<Result<Entry, String>>, String>;
+
+struct Job {
+    id: usize,
+    dir: PathBuf,
+    depth: usize,
+}
+
+/// Walk the paths in the same order as `serial`, while a pool of threads
+/// reads directories ahead of the walk. Every directory is queued as soon
+/// as its parent has been read, so on slow file systems many reads are in
+/// flight while the entries are visited here, one at a time
+pub fn parallel(paths: &[String], opts: &WalkOptions, visit: &mut Visit) -> MyResult<()> {
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let (listing_tx, listing_rx) = mpsc::channel();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+
+    thread::scope(|scope| {
+        for _ in 0..opts.threads {
+            let job_rx = Arc::clone(&job_rx);
+            let listing_tx = listing_tx.clone();
+            scope.spawn(move || loop {
+                let job = match job_rx.lock() {
+                    Ok(jobs) => jobs.recv(),
+                    Err(_) => return,
+                };
+                let Ok(job) = job else { return };
+                let listing = read_dir(&job.dir, job.depth, opts);
+                if listing_tx.send((job.id, listing)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(listing_tx);
+
+        let mut walker = Parallel {
+            opts,
+            jobs: job_tx,
+            listings: listing_rx,
+            ready: HashMap::new(),
+            abandoned: HashSet::new(),
+            next_id: 0,
+            ancestors: vec![],
+            root_dev: 0,
+        };
+        let result = paths
+            .iter()
+            .try_for_each(|path| walker.walk_root(path, visit));
+        // Closing the queue lets the workers finish
+        drop(walker);
+        result
+    })
+}
+
+fn read_dir(dir: &Path, depth: usize, opts: &WalkOptions) -> Listing {
+    let read_entry = |entry: io::Result<fs::DirEntry>| {
+        let entry = entry.map_err(|e| io_error(dir, e))?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| io_error(&path, e))?;
+        let path_is_symlink = file_type.is_symlink();
+        let file_type = if path_is_symlink && opts.follow_links {
+            fs::metadata(&path)
+                .map_err(|e| io_error(&path, e))?
+                .file_type()
+        } else {
+            file_type
+        };
+        Ok(Entry {
+            path,
+            depth,
+            file_type,
+            path_is_symlink,
+        })
+    };
+
+    let mut entries: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| io_error(dir, e))?
+        .map(read_entry)
+        .collect();
+    if opts.sort {
+        entries.sort_by(|a, b| match (a, b) {
+            (Ok(a), Ok(b)) => a.file_name().cmp(b.file_name()),
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => Ordering::Equal,
+        });
+    }
+    Ok(entries)
+}
+
+/// Word errors the way WalkDir does, so both walkers report the same
+fn io_error(path: &Path, e: io::Error) -> String {
+    format!("IO error for operation on {}: {}", path.display(), e)
+}
+
+struct Parallel<'a> {
+    opts: &'a WalkOptions,
+    jobs: mpsc::Sender<Job>,
+    listings: mpsc::Receiver<(usize, Listing)>,
+    /// Listings that came back before the walk got to them
+    ready: HashMap<usize, Listing>,
+    /// Listings the walk no longer needs, dropped when they come back
+    abandoned: HashSet<usize>,
+    next_id: usize,
+    /// The directories being walked, with their device and inode when
+    /// links are followed, to find loops
+    ancestors: Vec<(PathBuf, Option<(u64, u64)>)>,
+    root_dev: u64,
+}
+
+impl Parallel<'_> {
+    fn walk_root(&mut self, path: &str, visit: &mut Visit) -> MyResult<()> {
+        let path = PathBuf::from(path);
+        let entry = match fs::symlink_metadata(&path) {
+            Ok(meta) => {
+                let path_is_symlink = meta.file_type().is_symlink();
+                // Like WalkDir, a link given as a path is followed
+                let file_type = match fs::metadata(&path) {
+                    Ok(target) if path_is_symlink => target.file_type(),
+                    _ => meta.file_type(),
+                };
+                Entry {
+                    path,
+                    depth: 0,
+                    file_type,
+                    path_is_symlink,
+                }
+            }
+            Err(e) => {
+                visit(Err(io_error(&path, e)))?;
+                return Ok(());
+            }
+        };
+        self.root_dev = entry.metadata().map_or(0, |meta| meta.dev());
+        self.ancestors.clear();
+        let listing = self.prefetch(&entry);
+        self.walk_entry(entry, listing, visit)
+    }
+
+    /// Queue the read of `entry` if the walk is going to descend into it
+    fn prefetch(&mut self, entry: &Entry) -> Option<usize> {
+        if !entry.file_type().is_dir() || entry.depth() >= self.opts.max_depth {
+            return None;
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        // The workers only stop once the queue is closed
+        let _ = self.jobs.send(Job {
+            id,
+            dir: entry.path().to_path_buf(),
+            depth: entry.depth() + 1,
+        });
+        Some(id)
+    }
+
+    fn wait(&mut self, id: usize) -> Listing {
+        loop {
+            if let Some(listing) = self.ready.remove(&id) {
+                return listing;
+            }
+            match self.listings.recv() {
+                Ok((done, listing)) => {
+                    if !self.abandoned.remove(&done) {
+                        self.ready.insert(done, listing);
+                    }
+                }
+                Err(_) => return Err("Directory reader threads stopped".to_string()),
+            }
+        }
+    }
+
+    fn abandon(&mut self, id: usize) {
+        if self.ready.remove(&id).is_none() {
+            self.abandoned.insert(id);
+        }
+    }
+
+    fn walk_entry(
+        &mut self,
+        entry: Entry,
+        mut listing: Option<usize>,
+        visit: &mut Visit,
+    ) -> MyResult<()> {
+        // Like WalkDir, a loop is reported in place of the entry
+        let id = match self.identify(&entry) {
+            Ok(id) => id,
+            Err(e) => {
+                if let Some(listing) = listing {
+                    self.abandon(listing);
+                }
+                visit(Err(e))?;
+                return Ok(());
+            }
+        };
+
+        let visible = entry.depth() >= self.opts.min_depth;
+        if visible && !self.opts.contents_first && visit(Ok(&entry))? {
+            if let Some(id) = listing.take() {
+                self.abandon(id);
+            }
+        }
+
+        if let Some(listing) = listing {
+            let other_device = matches!(id, Some((dev, _)) if dev != self.root_dev);
+            if self.opts.same_file_system && other_device {
+                self.abandon(listing);
+            } else {
+                self.ancestors.push((entry.path().to_path_buf(), id));
+                let result = self.walk_children(listing, visit);
+                self.ancestors.pop();
+                result?;
+            }
+        }
+
+        if visible && self.opts.contents_first {
+            visit(Ok(&entry))?;
+        }
+        Ok(())
+    }
+
+    /// The device and inode of a directory when the walk needs them,
+    /// checking that it does not lead back to one of its ancestors
+    fn identify(&self, entry: &Entry) -> Result<Option<(u64, u64)>, String> {
+        if !entry.file_type().is_dir() || !(self.opts.follow_links || self.opts.same_file_system) {
+            return Ok(None);
+        }
+        let meta = entry.metadata().map_err(|e| io_error(entry.path(), e))?;
+        let id = Some((meta.dev(), meta.ino()));
+        if self.opts.follow_links {
+            if let Some((ancestor, _)) = self.ancestors.iter().find(|(_, a)| *a == id) {
+                return Err(format!(
+                    "File system loop found: {} points to an ancestor {}",
+                    entry.path().display(),
+                    ancestor.display()
+                ));
+            }
+        }
+        Ok(id)
+    }
+
+    fn walk_children(&mut self, id: usize, visit: &mut Visit) -> MyResult<()> {
+        let children = match self.wait(id) {
+            Ok(children) => children,
+            Err(e) => {
+                visit(Err(e))?;
+                return Ok(());
+            }
+        };
+        // Queue every subdirectory before visiting any, so their reads
+        // overlap with the walk of their siblings
+        let children: Vec<_> = children
+            .into_iter()
+            .map(|child| {
+                child.map(|entry| {
+                    let listing = self.prefetch(&entry);
+                    (entry, listing)
+                })
+            })
+            .collect();
+        for child in children {
+            match child {
+                Ok((entry, listing)) => self.walk_entry(entry, listing, visit)?,
+                Err(e) => {
+                    visit(Err(e))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
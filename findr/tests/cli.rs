@@ -1,3 +1,6 @@
+// The tests keep the style they were written in
+#![allow(clippy::needless_borrows_for_generic_args, mismatched_lifetime_syntaxes)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -30,7 +33,7 @@ fn skips_bad_dir() -> TestResult {
     Command::cargo_bin(PRG)?
         .arg(&bad)
         .assert()
-        .success()
+        .failure()
         .stderr(predicate::str::is_match(expected)?);
     Ok(())
 }
@@ -39,10 +42,10 @@ fn skips_bad_dir() -> TestResult {
 #[test]
 fn dies_bad_name() -> TestResult {
     Command::cargo_bin(PRG)?
-        .args(&["--name", "*.csv"])
+        .args(&["--name", "[a"])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("Invalid --name \"*.csv\""));
+        .stderr(predicate::str::contains("Invalid --name \"[a\""));
     Ok(())
 }
 
@@ -228,7 +231,7 @@ fn type_f_l() -> TestResult {
 #[test]
 fn name_csv() -> TestResult {
     run(
-        &["tests/inputs", "-n", ".*[.]csv"],
+        &["tests/inputs", "-n", "*.csv"],
         "tests/expected/name_csv.txt",
     )
 }
@@ -237,7 +240,7 @@ fn name_csv() -> TestResult {
 #[test]
 fn name_csv_mp3() -> TestResult {
     run(
-        &["tests/inputs", "-n", ".*[.]csv", "-n", ".*[.]mp3"],
+        &["tests/inputs", "-n", "*.csv", "-n", "*.mp3"],
         "tests/expected/name_csv_mp3.txt",
     )
 }
@@ -246,7 +249,7 @@ fn name_csv_mp3() -> TestResult {
 #[test]
 fn name_txt_path_a_d() -> TestResult {
     run(
-        &["tests/inputs/a", "tests/inputs/d", "--name", ".*.txt"],
+        &["tests/inputs/a", "tests/inputs/d", "--name", "*.txt"],
         "tests/expected/name_txt_path_a_d.txt",
     )
 }
@@ -261,7 +264,7 @@ fn name_a() -> TestResult {
 #[test]
 fn type_f_name_a() -> TestResult {
     run(
-        &["tests/inputs", "-t", "f", "-n", "a"],
+        &["tests/inputs", "-t", "f", "-n", "a*"],
         "tests/expected/type_f_name_a.txt",
     )
 }
@@ -281,6 +284,608 @@ fn path_g() -> TestResult {
     run(&["tests/inputs/g.csv"], "tests/expected/path_g.txt")
 }
 
+// --------------------------------------------------
+#[test]
+fn expr_or_not() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "(",
+            "-name",
+            "*csv*",
+            "-o",
+            "-iname",
+            "*.MP3",
+            ")",
+            "!",
+            "-type",
+            "l",
+        ],
+        "tests/expected/expr_or_not.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn expr_type_d_not_name() -> TestResult {
+    run(
+        &["tests/inputs", "-type", "d", "-a", "!", "-name", "[ab]"],
+        "tests/expected/expr_type_d_not_name.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn expr_regex() -> TestResult {
+    run(
+        &["tests/inputs", "-regex", ".*/[a-c]\\.[a-z]+"],
+        "tests/expected/expr_regex.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn name_glob_whole_basename() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "?.t*", "-o", "-iname", "[C-E].MP3"],
+        "tests/expected/name_glob_whole_basename.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn expr_iregex() -> TestResult {
+    run(
+        &["tests/inputs", "-iregex", ".*/[A-C]\\.(CSV|MP3)"],
+        "tests/expected/expr_iregex.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn expr_name_or_path() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "*.txt", "-o", "-path", "*a/b/*"],
+        "tests/expected/expr_name_or_path.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn expr_size() -> TestResult {
+    run(
+        &[
+            "tests/inputs",
+            "-size",
+            "2c",
+            "-o",
+            "-type",
+            "l",
+            "-size",
+            "-2",
+        ],
+        "tests/expected/expr_size.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn expr_empty() -> TestResult {
+    run(
+        &["tests/inputs", "-empty", "-o", "-type", "d", "-name", "c"],
+        "tests/expected/expr_empty.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn print0() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "-print0"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/f\0tests/inputs/f/f.txt\0");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn printf() -> TestResult {
+    run(
+        &["tests/inputs/a", "-type", "f", "-printf", "%f %s %d %h\\n"],
+        "tests/expected/printf.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_each() -> TestResult {
+    run(
+        &[
+            "tests/inputs/a",
+            "-type",
+            "f",
+            "-exec",
+            "echo",
+            "found",
+            "{}",
+            ";",
+        ],
+        "tests/expected/exec_each.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_batch() -> TestResult {
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a", "-type", "f", "-exec", "echo", "{}", "+"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut paths: Vec<&str> = stdout.trim_end().split(' ').collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            "tests/inputs/a/a.txt",
+            "tests/inputs/a/b/b.csv",
+            "tests/inputs/a/b/c/c.mp3"
+        ]
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn exec_status() -> TestResult {
+    // A failing command fails the run, and -exec ; is still false for -o
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "-exec", "false", ";", "-o", "-print"])
+        .assert()
+        .failure()
+        .stdout("tests/inputs/f\ntests/inputs/f/f.txt\n")
+        .stderr("");
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "-exec", "true", ";"])
+        .assert()
+        .success();
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "-exec", "false", "{}", "+"])
+        .assert()
+        .failure()
+        .stderr("");
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/f", "-exec", "no-such-command-here", ";"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no-such-command-here: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn delete() -> TestResult {
+    let tmp = tempfile::tempdir()?;
+    let root = tmp.path().join("root");
+    fs::create_dir_all(root.join("a/b"))?;
+    fs::write(root.join("a/b/c.tmp"), "")?;
+    fs::write(root.join("a/d.txt"), "")?;
+    fs::write(root.join("e.tmp"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["-name", "*.tmp", "-delete"])
+        .assert()
+        .success()
+        .stdout("");
+    assert!(!root.join("a/b/c.tmp").exists());
+    assert!(!root.join("e.tmp").exists());
+    assert!(root.join("a/d.txt").exists());
+
+    // Directories are removed depth-first, and only once they are empty
+    Command::cargo_bin(PRG)?
+        .arg(&root)
+        .args(["-name", "[ab]", "-delete"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Directory not empty"));
+    assert!(!root.join("a/b").exists());
+    assert!(root.join("a").exists());
+
+    Command::cargo_bin(PRG)?
+        .arg(&root)
+        .arg("-delete")
+        .assert()
+        .success();
+    assert!(!root.exists());
+
+    Command::cargo_bin(PRG)?
+        .args(["..", "--max_depth", "0", "-delete"])
+        .assert()
+        .failure()
+        .stderr("Refusing to delete \"..\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn prune() -> TestResult {
+    run(
+        &["tests/inputs", "-name", "[bd]", "-prune", "-o", "-print"],
+        "tests/expected/prune.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn xdev() -> TestResult {
+    run(
+        &["tests/inputs", "-xdev", "-type", "l"],
+        "tests/expected/type_l.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn follow_links() -> TestResult {
+    let tmp = tempfile::tempdir()?;
+    let root = tmp.path();
+    fs::create_dir(root.join("dir"))?;
+    fs::write(root.join("dir/file.txt"), "")?;
+    std::os::unix::fs::symlink("dir", root.join("link"))?;
+
+    Command::cargo_bin(PRG)?
+        .arg(root)
+        .args(["-name", "file.txt"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", root.join("dir/file.txt").display()));
+
+    let cmd = Command::cargo_bin(PRG)?
+        .arg("-L")
+        .arg(root)
+        .args(["-name", "file.txt"])
+        .assert()
+        .success();
+    let stdout = String::from_utf8(cmd.get_output().stdout.clone())?;
+    let mut lines: Vec<&str> = stdout.lines().collect();
+    lines.sort();
+    let expected = [root.join("dir/file.txt"), root.join("link/file.txt")];
+    assert_eq!(lines, expected.map(|path| path.display().to_string()));
+
+    // A loop is reported and makes the exit status fail
+    std::os::unix::fs::symlink("..", root.join("dir/up"))?;
+    Command::cargo_bin(PRG)?
+        .arg("-L")
+        .arg(root)
+        .args(["-name", "file.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("loop"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_delete_follow_links() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-L", "tests/inputs", "-delete"])
+        .assert()
+        .failure()
+        .stderr("-delete cannot be used with -L\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn maxdepth_mindepth() -> TestResult {
+    run(
+        &["tests/inputs", "-maxdepth", "1", "-mindepth", "1"],
+        "tests/expected/maxdepth_mindepth.txt",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn unlimited_depth() -> TestResult {
+    let tmp = tempfile::tempdir()?;
+    let deep = (0..15).fold(tmp.path().to_path_buf(), |dir, i| dir.join(i.to_string()));
+    fs::create_dir_all(&deep)?;
+    fs::write(deep.join("deep.txt"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .arg(tmp.path())
+        .args(["-name", "deep.txt"])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", deep.join("deep.txt").display()));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn depth_first() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["tests/inputs/a/b/c", "-depth"])
+        .assert()
+        .success()
+        .stdout("tests/inputs/a/b/c/c.mp3\ntests/inputs/a/b/c\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_depth() -> TestResult {
+    let tests = [
+        (vec!["-maxdepth", "-1"], "Invalid -maxdepth \"-1\""),
+        (vec!["-mindepth", "one"], "Invalid -mindepth \"one\""),
+        (vec!["-maxdepth"], "Missing argument to -maxdepth"),
+        (vec!["--max_depth", "x"], "Invalid --max_depth \"x\""),
+        (vec!["--min_depth", "1.5"], "Invalid --min_depth \"1.5\""),
+    ];
+    for (args, expected) in tests {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(format!("{}\n", expected));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn sort() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/sort.txt")?;
+    for jobs in ["1", "4"] {
+        Command::cargo_bin(PRG)?
+            .args(["-j", jobs, "--sort", "tests/inputs"])
+            .assert()
+            .success()
+            .stdout(expected.clone());
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn parallel_matches_serial() -> TestResult {
+    let tests = [
+        vec!["tests/inputs"],
+        vec!["tests/inputs", "-depth"],
+        vec!["tests/inputs", "-name", "[bd]", "-prune", "-o", "-print"],
+        vec![
+            "tests/inputs",
+            "-maxdepth",
+            "2",
+            "-mindepth",
+            "1",
+            "-type",
+            "f",
+        ],
+        vec!["-L", "tests/inputs/a", "tests/inputs/d"],
+        vec!["tests/inputs/a", "-printf", "%d %f\\n"],
+    ];
+    for args in tests {
+        let serial = Command::cargo_bin(PRG)?
+            .arg("--sort")
+            .args(&args)
+            .output()?;
+        Command::cargo_bin(PRG)?
+            .args(["-j4", "--sort"])
+            .args(&args)
+            .assert()
+            .code(serial.status.code().unwrap_or_default())
+            .stdout(serial.stdout)
+            .stderr(serial.stderr);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn parallel_skips_bad_dir() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error [23][)]", &bad);
+    Command::cargo_bin(PRG)?
+        .args(["-j", "2", &bad, "tests/inputs/f"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("f.txt"))
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_jobs() -> TestResult {
+    for jobs in ["0", "x", "1.5"] {
+        Command::cargo_bin(PRG)?
+            .args(["--jobs", jobs])
+            .assert()
+            .failure()
+            .stderr(format!("Invalid --jobs \"{}\"\n", jobs));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+fn dupes_dir() -> Result<tempfile::TempDir, Box<dyn std::error::Error>> {
+    let tmp = tempfile::tempdir()?;
+    let root = tmp.path();
+    fs::create_dir(root.join("sub"))?;
+    fs::write(root.join("a.txt"), "hello")?;
+    fs::write(root.join("sub/b.txt"), "hello")?;
+    fs::write(root.join("c.txt"), "hellp")?;
+    fs::write(root.join("big1"), vec![0; 10_000])?;
+    fs::write(root.join("big2"), vec![0; 10_000])?;
+    let mut big3 = vec![0; 10_000];
+    big3[9_999] = 1;
+    fs::write(root.join("big3"), big3)?;
+    fs::write(root.join("empty1"), "")?;
+    fs::write(root.join("empty2"), "")?;
+    Ok(tmp)
+}
+
+// --------------------------------------------------
+#[test]
+fn duplicates() -> TestResult {
+    let tmp = dupes_dir()?;
+    let root = tmp.path();
+    // The groups are in order of their first path
+    let expected = format!(
+        "{}\n{}\n\n{}\n{}\n",
+        root.join("a.txt").display(),
+        root.join("sub/b.txt").display(),
+        root.join("big1").display(),
+        root.join("big2").display(),
+    );
+    Command::cargo_bin(PRG)?
+        .args(["-j", "2", "--duplicates"])
+        .arg(root)
+        .assert()
+        .success()
+        .stdout(expected);
+
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates", "--min_size", "6"])
+        .arg(root)
+        .args(["-name", "big*"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "{}\n{}\n",
+            root.join("big1").display(),
+            root.join("big2").display()
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn duplicates_json() -> TestResult {
+    let tmp = dupes_dir()?;
+    let root = tmp.path();
+    let cmd = Command::cargo_bin(PRG)?
+        .args(["--duplicates", "--min_size", "0", "--format", "json"])
+        .arg(root)
+        .args(["-name", "empty*"])
+        .assert()
+        .success();
+    let groups: serde_json::Value = serde_json::from_slice(&cmd.get_output().stdout)?;
+    assert_eq!(
+        groups,
+        serde_json::json!([{
+            "size": 0,
+            "sha256": "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "paths": [root.join("empty1"), root.join("empty2")],
+        }])
+    );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+#[cfg(not(windows))]
+fn duplicates_hardlink() -> TestResult {
+    use std::os::unix::fs::MetadataExt;
+
+    let tmp = dupes_dir()?;
+    let root = tmp.path();
+    Command::cargo_bin(PRG)?
+        .args(["--duplicates", "--hardlink"])
+        .arg(root)
+        .args(["-name", "big*"])
+        .assert()
+        .success();
+    let ino = |name: &str| fs::metadata(root.join(name)).map(|meta| meta.ino());
+    assert_eq!(ino("big1")?, ino("big2")?);
+    assert_ne!(ino("big1")?, ino("big3")?);
+    assert_eq!(fs::read_dir(root)?.count(), 8);
+
+    // Hard links to the same file are not duplicates of each other
+    Command::cargo_bin(PRG)?
+        .arg("--duplicates")
+        .arg(root)
+        .args(["-name", "big*"])
+        .assert()
+        .success()
+        .stdout("");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_duplicates() -> TestResult {
+    let tests = [
+        (
+            vec!["--duplicates", ".", "-print"],
+            "Actions cannot be used with --duplicates",
+        ),
+        (
+            vec!["--duplicates", "--min_size", "1k"],
+            "Invalid --min_size \"1k\"",
+        ),
+    ];
+    for (args, expected) in tests {
+        Command::cargo_bin(PRG)?
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(format!("{}\n", expected));
+    }
+    Command::cargo_bin(PRG)?
+        .arg("--hardlink")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--duplicates"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_expr() -> TestResult {
+    let tests = [
+        (vec!["-name", "a", "-o"], "Expected an expression after -o"),
+        (vec!["(", "-type", "f"], "Missing closing \")\""),
+        (vec!["-foo"], "Unknown predicate \"-foo\""),
+        (vec!["-type", "x"], "Invalid -type \"x\""),
+        (vec!["-iname", "[a"], "Invalid -iname \"[a\""),
+        (vec!["-iregex", "*"], "Invalid -iregex \"*\""),
+        (vec!["-size", "1x"], "Invalid -size \"1x\""),
+        (vec!["-mtime", "+x"], "Invalid -mtime \"+x\""),
+        (vec!["-perm", "u+x"], "Invalid -perm \"u+x\""),
+        (vec!["-group"], "Missing argument to -group"),
+        (vec!["-exec", "echo", "{}"], "Missing argument to -exec"),
+        (vec!["-printf", "%z"], "Invalid -printf directive \"%z\""),
+    ];
+    for (args, expected) in tests {
+        Command::cargo_bin(PRG)?
+            .arg("tests/inputs")
+            .args(args)
+            .assert()
+            .failure()
+            .stderr(format!("{}\n", expected));
+    }
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 #[cfg(not(windows))]
@@ -302,7 +907,7 @@ fn unreadable_dir() -> TestResult {
     let cmd = Command::cargo_bin(PRG)?
         .arg("tests/inputs")
         .assert()
-        .success();
+        .failure();
     fs::remove_dir(dirname)?;
 
     let out = cmd.get_output();
@@ -1,11 +1,130 @@
+mod output;
+
 use clap::{App, Arg};
-use walkdir::WalkDir;
+use glob::Pattern;
+use ignore::gitignore::Gitignore;
+use output::Render;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::{self, DirEntry, Metadata};
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
 
 type MyResult<T> = Result<T, Box<dyn std::error::Error>>;
 
 pub struct Config {
     path: String,
     level: Option<usize>,
+    show_hidden: bool,
+    dirs_only: bool,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+    prune: bool,
+    gitignore: bool,
+    perms: bool,
+    owner: bool,
+    group: bool,
+    size: bool,
+    human: bool,
+    mtime: bool,
+    du: bool,
+    format: OutputFormat,
+    sort: Option<SortBy>,
+    reverse: bool,
+    dirs_first: bool,
+    files_first: bool,
+    follow: bool,
+    file_limit: Option<usize>,
+    no_report: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Name,
+    Version,
+    Size,
+    Mtime,
+    Ctime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Xml,
+    Html(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Directory,
+    File,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Directory => "directory",
+            Kind::File => "file",
+        }
+    }
+}
+
+/// One entry of the listing, which every output format is rendered from
+#[derive(Debug)]
+pub(crate) struct Node {
+    name: String,
+    /// What the entry is, or what it points to for a symlink
+    kind: Kind,
+    /// Where a symlink points
+    target: Option<String>,
+    meta: Option<Metadata>,
+    /// The entry's own size, or everything below it with --du
+    size: u64,
+    error: Option<String>,
+}
+
+impl Node {
+    fn new(name: String, kind: Kind, meta: Option<Metadata>) -> Self {
+        Node {
+            name,
+            kind,
+            target: None,
+            size: meta.as_ref().map_or(0, |meta| meta.len()),
+            meta,
+            error: None,
+        }
+    }
+
+    /// The type name used by the JSON and XML output
+    fn type_name(&self) -> &'static str {
+        match self.target {
+            Some(_) => "link",
+            None => self.kind.as_str(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Counts {
+    dirs: usize,
+    files: usize,
+    bytes: u64,
+}
+
+struct Walker<'a> {
+    config: &'a Config,
+    render: &'a mut dyn Render,
+    counts: Counts,
+    ignores: Vec<Gitignore>,
+    /// Device and inode of each directory being walked, to catch loops
+    ancestors: Vec<Option<(u64, u64)>>,
+    /// The --du total of every directory below the root, filled in when
+    /// the root is measured
+    sizes: HashMap<(u64, u64), u64>,
+    /// Whether each directory checked for --prune would list nothing
+    empty_dirs: HashMap<(u64, u64), bool>,
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -27,40 +146,663 @@ pub fn get_args() -> MyResult<Config> {
                 .help("directory level")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("all")
+                .short("a")
+                .long("all")
+                .help("Show hidden files"),
+        )
+        .arg(
+            Arg::with_name("dirs_only")
+                .short("d")
+                .long("dirs-only")
+                .help("List directories only"),
+        )
+        .arg(
+            Arg::with_name("include")
+                .value_name("PATTERN")
+                .short("P")
+                .long("pattern")
+                .help("List only files matching the glob PATTERN, e.g. '*.rs|*.toml'")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .value_name("PATTERN")
+                .short("I")
+                .long("ignore")
+                .help("Do not list files matching the glob PATTERN")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("prune")
+                .long("prune")
+                .help("Do not list directories left empty after filtering"),
+        )
+        .arg(
+            Arg::with_name("gitignore")
+                .long("gitignore")
+                .help("Filter by using .gitignore files"),
+        )
+        .arg(
+            Arg::with_name("perms")
+                .short("p")
+                .help("Print the protections for each file"),
+        )
+        .arg(
+            Arg::with_name("owner")
+                .short("u")
+                .help("Print the file owner or UID number"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .short("g")
+                .help("Print the file group or GID number"),
+        )
+        .arg(
+            Arg::with_name("size")
+                .short("s")
+                .help("Print the size in bytes of each file"),
+        )
+        .arg(
+            Arg::with_name("human")
+                .short("h")
+                .help("Print the size in a more human readable way"),
+        )
+        .arg(
+            Arg::with_name("mtime")
+                .short("D")
+                .help("Print the date of last modification"),
+        )
+        .arg(
+            Arg::with_name("du")
+                .long("du")
+                .help("Print directory sizes as the total size of their contents"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .short("J")
+                .help("Print the tree as JSON")
+                .conflicts_with_all(&["xml", "html"]),
+        )
+        .arg(
+            Arg::with_name("xml")
+                .short("X")
+                .help("Print the tree as XML")
+                .conflicts_with("html"),
+        )
+        .arg(
+            Arg::with_name("html")
+                .value_name("BASEHREF")
+                .short("H")
+                .help("Print the tree as an HTML page linking names below BASEHREF")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .value_name("TYPE")
+                .long("sort")
+                .help("Sort the output by TYPE instead of name")
+                .possible_values(&["name", "version", "size", "mtime", "ctime"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("reverse")
+                .short("r")
+                .help("Sort the output in reverse order"),
+        )
+        .arg(
+            Arg::with_name("unsorted")
+                .short("U")
+                .help("Leave files in directory order")
+                .conflicts_with("sort"),
+        )
+        .arg(
+            Arg::with_name("dirs_first")
+                .long("dirsfirst")
+                .help("List directories before files"),
+        )
+        .arg(
+            Arg::with_name("files_first")
+                .long("filesfirst")
+                .help("List files before directories")
+                .conflicts_with("dirs_first"),
+        )
+        .arg(
+            Arg::with_name("follow")
+                .short("l")
+                .help("Follow symbolic links to directories"),
+        )
+        .arg(
+            Arg::with_name("file_limit")
+                .value_name("N")
+                .long("filelimit")
+                .help("Do not descend into directories with more than N entries")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("no_report")
+                .long("noreport")
+                .help("Omit the file and directory report at the end"),
+        )
         .get_matches();
 
     let path = matches.value_of("path").unwrap().to_string();
-    let level = match matches.value_of("level") {
-        Some(v) => Some(v.to_string().parse::<usize>()),
-        None => None,
+    let level = matches
+        .value_of("level")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|_| "Invalid level, must be greater than 0.")?;
+    let file_limit = matches
+        .value_of("file_limit")
+        .map(parse_positive_int)
+        .transpose()
+        .map_err(|e| format!("Invalid file limit \"{}\"", e))?;
+    let include = parse_patterns(matches.value_of("include"))?;
+    let exclude = parse_patterns(matches.value_of("exclude"))?;
+    let format = if matches.is_present("json") {
+        OutputFormat::Json
+    } else if matches.is_present("xml") {
+        OutputFormat::Xml
+    } else if let Some(base) = matches.value_of("html") {
+        OutputFormat::Html(base.trim_end_matches('/').to_string())
+    } else {
+        OutputFormat::Text
+    };
+    let sort = match matches.value_of("sort") {
+        _ if matches.is_present("unsorted") => None,
+        Some("version") => Some(SortBy::Version),
+        Some("size") => Some(SortBy::Size),
+        Some("mtime") => Some(SortBy::Mtime),
+        Some("ctime") => Some(SortBy::Ctime),
+        _ => Some(SortBy::Name),
+    };
+
+    Ok(Config {
+        path,
+        level,
+        show_hidden: matches.is_present("all"),
+        dirs_only: matches.is_present("dirs_only"),
+        include,
+        exclude,
+        prune: matches.is_present("prune"),
+        gitignore: matches.is_present("gitignore"),
+        perms: matches.is_present("perms"),
+        owner: matches.is_present("owner"),
+        group: matches.is_present("group"),
+        size: ["size", "human", "du"]
+            .iter()
+            .any(|v| matches.is_present(v)),
+        human: matches.is_present("human"),
+        mtime: matches.is_present("mtime"),
+        du: matches.is_present("du"),
+        format,
+        sort,
+        reverse: matches.is_present("reverse"),
+        dirs_first: matches.is_present("dirs_first"),
+        files_first: matches.is_present("files_first"),
+        follow: matches.is_present("follow"),
+        file_limit,
+        no_report: matches.is_present("no_report"),
+    })
+}
+
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match val.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
     }
-    .transpose()
-    .unwrap();
-    Ok(Config { path, level })
+}
+
+/// Split a tree-style `a|b` pattern list into globs
+fn parse_patterns(val: Option<&str>) -> MyResult<Vec<Pattern>> {
+    val.map(|val| val.split('|').collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|glob| Pattern::new(glob).map_err(|e| From::from(format!("{}: {}", glob, e))))
+        .collect()
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    // echo "│   ├──"
-    let mut depth = 0;
-    let mut padding = String::from("");
-    for entry in WalkDir::new(config.path).max_depth(2) {
-        match entry {
-            Ok(entry) => {
-                // println!("{} {}", entry.depth(), entry.path().display());
-                if entry.depth() == 0 {
-                    println!("{}", entry.path().display());
-                } else if entry.depth() == 1 {
-                    println!("├── {}", entry.file_name().to_str().unwrap());
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut render = output::renderer(&config, &mut out);
+    let mut walker = Walker {
+        config: &config,
+        render: render.as_mut(),
+        counts: Counts::default(),
+        ignores: vec![],
+        ancestors: vec![],
+        sizes: HashMap::new(),
+        empty_dirs: HashMap::new(),
+    };
+
+    let root = Path::new(&config.path);
+    // Like tree, a root that cannot be listed is shown as such, and then
+    // fails the run
+    let root_error = fs::read_dir(root).err();
+    let node = Node::new(
+        config.path.clone(),
+        Kind::Directory,
+        fs::metadata(root).ok(),
+    );
+    walker.counts.bytes = walker.walk_dir(node, root, 0, true)?;
+    walker.render.finish(&walker.counts)?;
+
+    drop(render);
+    out.flush()?;
+    match root_error {
+        Some(e) => Err(From::from(format!("{}: {}", config.path, e))),
+        None => Ok(()),
+    }
+}
+
+impl Walker<'_> {
+    /// Render `node`, the directory at `path` found at `depth`, followed by
+    /// everything below it that -L allows, and return its size. Only one
+    /// directory's entries per level are held at a time, so apart from a
+    /// number per directory for --du and --prune, memory stays bounded by
+    /// the depth and width of the tree rather than its size
+    fn walk_dir(
+        &mut self,
+        mut node: Node,
+        path: &Path,
+        depth: usize,
+        last: bool,
+    ) -> io::Result<u64> {
+        if !self.enter(path) {
+            node.error = Some("recursive, not followed".to_string());
+            self.render.node(&node, last)?;
+            self.render.end(&node)?;
+            return Ok(node.size);
+        }
+        let pushed = self.push_gitignore(path);
+
+        let mut children = vec![];
+        if self.config.level.is_none_or(|level| depth < level) {
+            match self.children(path) {
+                Ok(entries)
+                    if depth > 0
+                        && self
+                            .config
+                            .file_limit
+                            .is_some_and(|limit| entries.len() > limit) =>
+                {
+                    node.error = Some(format!(
+                        "{} entries exceeds filelimit, not opened",
+                        entries.len()
+                    ));
+                }
+                Ok(entries) => children = entries,
+                Err(_) => node.error = Some("error opening dir".to_string()),
+            }
+        }
+
+        // A directory's line comes before its contents, so with --du the
+        // totals are worked out ahead of walking them, all of them at once
+        // at the root
+        if self.config.du {
+            node.size += self.subtree_size(path);
+        }
+
+        self.render.node(&node, last)?;
+        let count = children.len();
+        for (i, entry) in children.iter().enumerate() {
+            self.walk_entry(entry, depth + 1, i + 1 == count)?;
+        }
+        self.render.end(&node)?;
+
+        if pushed {
+            self.ignores.pop();
+        }
+        self.leave();
+        Ok(node.size)
+    }
+
+    fn walk_entry(&mut self, entry: &DirEntry, depth: usize, last: bool) -> io::Result<()> {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = entry.path();
+        let meta = match entry.metadata() {
+            Ok(meta) => meta,
+            Err(e) => return self.failed_entry(name, None, e, last),
+        };
+
+        let node = if !meta.file_type().is_symlink() {
+            if meta.is_dir() {
+                self.counts.dirs += 1;
+                let node = Node::new(name, Kind::Directory, Some(meta));
+                return self.walk_dir(node, &path, depth, last).map(|_| ());
+            }
+            self.counts.files += 1;
+            Node::new(name, Kind::File, Some(meta))
+        } else {
+            let target = match fs::read_link(&path) {
+                Ok(target) => target.to_string_lossy().into_owned(),
+                Err(e) => return self.failed_entry(name, Some(meta), e, last),
+            };
+            let mut node = match fs::metadata(&path) {
+                Ok(resolved) if resolved.is_dir() => {
+                    self.counts.dirs += 1;
+                    Node::new(name, Kind::Directory, Some(meta))
+                }
+                Ok(_) => {
+                    self.counts.files += 1;
+                    Node::new(name, Kind::File, Some(meta))
+                }
+                Err(_) => {
+                    self.counts.files += 1;
+                    let mut node = Node::new(name, Kind::File, Some(meta));
+                    node.error = Some("broken link".to_string());
+                    node
+                }
+            };
+            node.target = Some(target);
+            if node.kind == Kind::Directory && self.config.follow {
+                return self.walk_dir(node, &path, depth, last).map(|_| ());
+            }
+            node
+        };
+
+        self.render.node(&node, last)?;
+        self.render.end(&node)
+    }
+
+    /// Show an entry that could not be looked into with why, and carry on
+    fn failed_entry(
+        &mut self,
+        name: String,
+        meta: Option<Metadata>,
+        error: io::Error,
+        last: bool,
+    ) -> io::Result<()> {
+        self.counts.files += 1;
+        let mut node = Node::new(name, Kind::File, meta);
+        node.error = Some(error.to_string());
+        self.render.node(&node, last)?;
+        self.render.end(&node)
+    }
+
+    /// Note that the directory at `path` is being walked, unless it already
+    /// is further up, in which case return false and leave things as they are
+    fn enter(&mut self, path: &Path) -> bool {
+        let id = dir_id(path);
+        if id.is_some() && self.ancestors.contains(&id) {
+            return false;
+        }
+        self.ancestors.push(id);
+        true
+    }
+
+    fn leave(&mut self) {
+        self.ancestors.pop();
+    }
+
+    /// Whether the walk goes inside `entry`, a directory or, with -l, a
+    /// symlink to one
+    fn descends(&self, entry: &DirEntry) -> bool {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_symlink() => self.config.follow && entry.path().is_dir(),
+            Ok(file_type) => file_type.is_dir(),
+            Err(_) => false,
+        }
+    }
+
+    /// Size of everything that would be listed below `path`, ignoring -L.
+    /// Each directory is only measured once, bottom-up, as part of the
+    /// first total that includes it
+    fn subtree_size(&mut self, path: &Path) -> u64 {
+        let id = dir_id(path);
+        if let Some(&size) = id.and_then(|id| self.sizes.get(&id)) {
+            return size;
+        }
+        let mut total = 0;
+        for entry in self.visible_children(path).unwrap_or_default() {
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            total += meta.len();
+            let path = entry.path();
+            if self.descends(&entry) && self.enter(&path) {
+                let pushed = self.push_gitignore(&path);
+                total += self.subtree_size(&path);
+                if pushed {
+                    self.ignores.pop();
+                }
+                self.leave();
+            }
+        }
+        if let Some(id) = id {
+            self.sizes.insert(id, total);
+        }
+        total
+    }
+
+    /// The entries of `path` that survive every filter, in listing order
+    fn children(&mut self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let children = self.visible_children(path)?;
+        Ok(self.sort_entries(children))
+    }
+
+    /// Order `entries` as --sort, -r, -U, --dirsfirst and --filesfirst ask
+    fn sort_entries(&self, entries: Vec<DirEntry>) -> Vec<DirEntry> {
+        let config = self.config;
+        let mut keyed: Vec<_> = entries
+            .into_iter()
+            .map(|entry| {
+                let meta = entry.metadata().ok();
+                (entry, meta)
+            })
+            .collect();
+
+        if let Some(sort) = config.sort {
+            keyed.sort_by(|(a, a_meta), (b, b_meta)| {
+                let by_name = || a.file_name().cmp(&b.file_name());
+                let order = match sort {
+                    SortBy::Name => by_name(),
+                    SortBy::Version => version_cmp(
+                        &a.file_name().to_string_lossy(),
+                        &b.file_name().to_string_lossy(),
+                    ),
+                    // Largest first, like `tree --sort=size`
+                    SortBy::Size => {
+                        let size = |meta: &Option<Metadata>| meta.as_ref().map(|m| m.len());
+                        size(b_meta).cmp(&size(a_meta)).then_with(by_name)
+                    }
+                    SortBy::Mtime => {
+                        let mtime = |meta: &Option<Metadata>| {
+                            meta.as_ref().map(|m| (m.mtime(), m.mtime_nsec()))
+                        };
+                        mtime(a_meta).cmp(&mtime(b_meta)).then_with(by_name)
+                    }
+                    SortBy::Ctime => {
+                        let ctime = |meta: &Option<Metadata>| {
+                            meta.as_ref().map(|m| (m.ctime(), m.ctime_nsec()))
+                        };
+                        ctime(a_meta).cmp(&ctime(b_meta)).then_with(by_name)
+                    }
+                };
+                if config.reverse {
+                    order.reverse()
                 } else {
-                    depth = entry.depth();
-                    padding = "   ".repeat(depth - 1);
-                    println!("│{}├── {}", padding, entry.file_name().to_str().unwrap());
+                    order
+                }
+            });
+        }
+
+        // Stable sorts, so each group keeps the order chosen above
+        if config.dirs_first {
+            keyed.sort_by_key(|(entry, _)| !is_dir(entry));
+        } else if config.files_first {
+            keyed.sort_by_key(|(entry, _)| is_dir(entry));
+        }
+
+        keyed.into_iter().map(|(entry, _)| entry).collect()
+    }
+
+    /// The entries of `path` that survive every filter, in directory order
+    fn visible_children(&mut self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut children = vec![];
+        for entry in fs::read_dir(path)? {
+            let entry = entry?;
+            if !self.is_visible(&entry) {
+                continue;
+            }
+            if self.config.prune && self.descends(&entry) && self.is_empty_dir(&entry.path()) {
+                continue;
+            }
+            children.push(entry);
+        }
+        Ok(children)
+    }
+
+    fn is_visible(&self, entry: &DirEntry) -> bool {
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let is_dir = is_dir(entry);
+
+        if !self.config.show_hidden && name.starts_with('.') {
+            return false;
+        }
+        if self.config.dirs_only && !is_dir {
+            return false;
+        }
+        if self.config.exclude.iter().any(|p| p.matches(&name)) {
+            return false;
+        }
+        if !is_dir
+            && !self.config.include.is_empty()
+            && !self.config.include.iter().any(|p| p.matches(&name))
+        {
+            return false;
+        }
+
+        let path = entry.path();
+        for gitignore in self.ignores.iter().rev() {
+            let matched = gitignore.matched(&path, is_dir);
+            if matched.is_ignore() {
+                return false;
+            }
+            if matched.is_whitelist() {
+                break;
+            }
+        }
+        true
+    }
+
+    /// Whether nothing would be listed under `path` once filters apply,
+    /// checked once per directory however many levels ask
+    fn is_empty_dir(&mut self, path: &Path) -> bool {
+        let id = dir_id(path);
+        if let Some(&empty) = id.and_then(|id| self.empty_dirs.get(&id)) {
+            return empty;
+        }
+        if !self.enter(path) {
+            return false;
+        }
+        let pushed = self.push_gitignore(path);
+        let empty = match self.visible_children(path) {
+            Ok(children) => children.is_empty(),
+            Err(_) => false,
+        };
+        if pushed {
+            self.ignores.pop();
+        }
+        self.leave();
+        if let Some(id) = id {
+            self.empty_dirs.insert(id, empty);
+        }
+        empty
+    }
+
+    /// Start honouring the `.gitignore` in `dir`, if there is one
+    fn push_gitignore(&mut self, dir: &Path) -> bool {
+        if !self.config.gitignore {
+            return false;
+        }
+        let file = dir.join(".gitignore");
+        if !file.is_file() {
+            return false;
+        }
+        let (gitignore, err) = Gitignore::new(&file);
+        if let Some(e) = err {
+            eprintln!("{}: {}", file.display(), e);
+        }
+        self.ignores.push(gitignore);
+        true
+    }
+}
+
+/// The device and inode of the directory at `path`, following links
+fn dir_id(path: &Path) -> Option<(u64, u64)> {
+    fs::metadata(path).ok().map(|meta| (meta.dev(), meta.ino()))
+}
+
+/// Whether `entry` is a directory or a symlink to one
+fn is_dir(entry: &DirEntry) -> bool {
+    match entry.file_type() {
+        Ok(file_type) if file_type.is_symlink() => entry.path().is_dir(),
+        Ok(file_type) => file_type.is_dir(),
+        Err(_) => false,
+    }
+}
+
+/// Compare names the way `ls -v` does, so "file9" sorts before "file10"
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let x = take_number(&mut a_chars);
+                let y = take_number(&mut b_chars);
+                let (x_trim, y_trim) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let order = x_trim
+                    .len()
+                    .cmp(&y_trim.len())
+                    .then_with(|| x_trim.cmp(y_trim));
+                if order != Ordering::Equal {
+                    return order;
                 }
             }
-            Err(e) => eprintln!("{}", e),
+            (Some(x), Some(y)) => {
+                let order = x.cmp(y);
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
         }
     }
-    Ok(())
 }
 
-fn display() {}
+#[cfg(test)]
+mod tests {
+    use super::version_cmp;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn test_version_cmp() {
+        assert_eq!(version_cmp("a", "a"), Ordering::Equal);
+        assert_eq!(version_cmp("a", "b"), Ordering::Less);
+        assert_eq!(version_cmp("file9", "file10"), Ordering::Less);
+        assert_eq!(version_cmp("file10", "file9"), Ordering::Greater);
+        assert_eq!(version_cmp("v1.2.10", "v1.10.2"), Ordering::Less);
+        assert_eq!(version_cmp("file", "file1"), Ordering::Less);
+        assert_eq!(version_cmp("a10b", "a10a"), Ordering::Greater);
+
+        // Equal numbers fall back to the plain comparison for a total order
+        assert_eq!(version_cmp("a01", "a1"), Ordering::Less);
+    }
+}
@@ -0,0 +1,564 @@
+use crate::{Config, Counts, Kind, Node, OutputFormat};
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+
+/// The metadata columns turned on in `config` for a single node
+#[derive(Debug, Default)]
+struct Columns {
+    mode: Option<String>,
+    prot: Option<String>,
+    user: Option<String>,
+    group: Option<String>,
+    size: Option<u64>,
+    time: Option<String>,
+}
+
+impl Columns {
+    fn new(node: &Node, config: &Config) -> Self {
+        let Some(meta) = &node.meta else {
+            return Columns::default();
+        };
+        let mut columns = Columns::default();
+
+        if config.perms {
+            columns.mode = Some(format!("{:04o}", meta.mode() & 0o7777));
+            columns.prot = Some(format_mode(meta));
+        }
+        if config.owner {
+            let uid = meta.uid();
+            columns.user = Some(
+                users::get_user_by_uid(uid)
+                    .map(|u| u.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| uid.to_string()),
+            );
+        }
+        if config.group {
+            let gid = meta.gid();
+            columns.group = Some(
+                users::get_group_by_gid(gid)
+                    .map(|g| g.name().to_string_lossy().into_owned())
+                    .unwrap_or_else(|| gid.to_string()),
+            );
+        }
+        if config.size {
+            columns.size = Some(node.size);
+        }
+        if config.mtime {
+            if let Ok(modified) = meta.modified() {
+                let modified: DateTime<Local> = DateTime::from(modified);
+                columns.time = Some(modified.format("%b %d %H:%M").to_string());
+            }
+        }
+        columns
+    }
+}
+
+/// Receives the listing while it is walked: `node` for every entry, parents
+/// before children, then `end` once everything below that entry is done
+pub(crate) trait Render {
+    fn node(&mut self, node: &Node, last: bool) -> io::Result<()>;
+    fn end(&mut self, node: &Node) -> io::Result<()>;
+    fn finish(&mut self, counts: &Counts) -> io::Result<()>;
+}
+
+pub(crate) fn renderer<'a>(config: &'a Config, out: &'a mut dyn Write) -> Box<dyn Render + 'a> {
+    match &config.format {
+        OutputFormat::Text => Box::new(Lines::new(out, config, None)),
+        OutputFormat::Html(base) => Box::new(Lines::new(out, config, Some(base))),
+        OutputFormat::Json => Box::new(Json {
+            out,
+            config,
+            frames: vec![],
+        }),
+        OutputFormat::Xml => Box::new(Xml {
+            out,
+            config,
+            frames: vec![],
+        }),
+    }
+}
+
+// --------------------------------------------------
+// Text and HTML share the connector layout, HTML just wraps names in links
+
+struct Level {
+    last: bool,
+    /// Path below the root, used for HTML links
+    rel: String,
+}
+
+struct Lines<'a> {
+    out: &'a mut dyn Write,
+    config: &'a Config,
+    base: Option<&'a str>,
+    levels: Vec<Level>,
+}
+
+impl<'a> Lines<'a> {
+    fn new(out: &'a mut dyn Write, config: &'a Config, base: Option<&'a str>) -> Self {
+        Lines {
+            out,
+            config,
+            base,
+            levels: vec![],
+        }
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        writeln!(self.out, "<!DOCTYPE html>")?;
+        writeln!(self.out, "<html>")?;
+        writeln!(self.out, "<head>")?;
+        writeln!(self.out, "<meta charset=\"utf-8\">")?;
+        writeln!(self.out, "<title>Directory Tree</title>")?;
+        writeln!(self.out, "</head>")?;
+        writeln!(self.out, "<body>")?;
+        writeln!(self.out, "<h1>Directory Tree</h1>")?;
+        writeln!(self.out, "<p>")
+    }
+}
+
+impl Render for Lines<'_> {
+    fn node(&mut self, node: &Node, last: bool) -> io::Result<()> {
+        let meta = format_meta(node, self.config);
+
+        let Some(parent) = self.levels.last() else {
+            match self.base {
+                Some(base) => {
+                    self.write_header()?;
+                    write!(
+                        self.out,
+                        "{}<a href=\"{}\">{}</a>",
+                        escape(&meta),
+                        escape(base),
+                        escape(base)
+                    )?;
+                }
+                None => write!(self.out, "{}{}", meta, node.name)?,
+            }
+            if let Some(error) = &node.error {
+                write!(self.out, " [{}]", error)?;
+            }
+            writeln!(
+                self.out,
+                "{}",
+                if self.base.is_some() { "<br>" } else { "" }
+            )?;
+            self.levels.push(Level {
+                last,
+                rel: String::new(),
+            });
+            return Ok(());
+        };
+
+        let rel = format!("{}/{}", parent.rel, url_encode(&node.name));
+        let prefix: String = self.levels[1..]
+            .iter()
+            .map(|level| if level.last { "    " } else { "│   " })
+            .collect();
+        let connector = if last { "└── " } else { "├── " };
+
+        match self.base {
+            Some(base) => {
+                let href = match node.kind {
+                    Kind::Directory => format!("{}{}/", base, rel),
+                    Kind::File => format!("{}{}", base, rel),
+                };
+                write!(
+                    self.out,
+                    "{}{}{}<a href=\"{}\">{}</a>",
+                    prefix,
+                    connector,
+                    escape(&meta),
+                    escape(&href),
+                    escape(&node.name)
+                )?;
+            }
+            None => write!(self.out, "{}{}{}{}", prefix, connector, meta, node.name)?,
+        }
+        if let Some(target) = &node.target {
+            match self.base {
+                Some(_) => write!(self.out, " -&gt; {}", escape(target))?,
+                None => write!(self.out, " -> {}", target)?,
+            }
+        }
+        if let Some(error) = &node.error {
+            write!(self.out, "  [{}]", error)?;
+        }
+        writeln!(
+            self.out,
+            "{}",
+            if self.base.is_some() { "<br>" } else { "" }
+        )?;
+
+        self.levels.push(Level { last, rel });
+        Ok(())
+    }
+
+    fn end(&mut self, _node: &Node) -> io::Result<()> {
+        self.levels.pop();
+        Ok(())
+    }
+
+    fn finish(&mut self, counts: &Counts) -> io::Result<()> {
+        let report = format_report(counts, self.config);
+        if self.base.is_none() {
+            if !self.config.no_report {
+                writeln!(self.out, "\n{}", report)?;
+            }
+            return Ok(());
+        }
+
+        writeln!(self.out, "</p>")?;
+        if !self.config.no_report {
+            writeln!(self.out, "<hr>")?;
+            writeln!(self.out, "<p>")?;
+            writeln!(self.out, "{}<br>", escape(&report))?;
+            writeln!(self.out, "</p>")?;
+        }
+        writeln!(self.out, "</body>")?;
+        writeln!(self.out, "</html>")
+    }
+}
+
+/// The bracketed metadata columns shown before a name, if any are on
+fn format_meta(node: &Node, config: &Config) -> String {
+    let columns = Columns::new(node, config);
+    let mut fields = vec![];
+
+    if let Some(prot) = columns.prot {
+        fields.push(prot);
+    }
+    if let Some(user) = columns.user {
+        fields.push(format!("{:<8}", user));
+    }
+    if let Some(group) = columns.group {
+        fields.push(format!("{:<8}", group));
+    }
+    if let Some(size) = columns.size {
+        fields.push(format_size(size, config));
+    }
+    if let Some(time) = columns.time {
+        fields.push(time);
+    }
+
+    if fields.is_empty() {
+        String::new()
+    } else {
+        format!("[{}]  ", fields.join(" "))
+    }
+}
+
+fn format_size(size: u64, config: &Config) -> String {
+    if config.human {
+        human_size(size)
+    } else {
+        format!("{:>11}", size)
+    }
+}
+
+fn format_report(counts: &Counts, config: &Config) -> String {
+    let mut report = format!(
+        "{} director{}",
+        counts.dirs,
+        if counts.dirs == 1 { "y" } else { "ies" }
+    );
+    if !config.dirs_only {
+        report += &format!(
+            ", {} file{}",
+            counts.files,
+            if counts.files == 1 { "" } else { "s" },
+        );
+    }
+    if config.du {
+        report = format!(
+            "{} used in {}",
+            format_size(counts.bytes, config).trim_start(),
+            report
+        );
+    }
+    report
+}
+
+// --------------------------------------------------
+// JSON, shaped like `tree -J` with one entry per line
+
+#[derive(Debug, Serialize)]
+struct JsonNode<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+struct JsonReport {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    directories: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    files: Option<usize>,
+}
+
+impl<'a> JsonNode<'a> {
+    fn new(node: &'a Node, config: &Config) -> Self {
+        let columns = Columns::new(node, config);
+        JsonNode {
+            kind: node.type_name(),
+            name: &node.name,
+            target: node.target.as_deref(),
+            mode: columns.mode,
+            prot: columns.prot,
+            user: columns.user,
+            group: columns.group,
+            size: columns.size,
+            time: columns.time,
+            error: node.error.as_deref(),
+        }
+    }
+}
+
+struct Json<'a> {
+    out: &'a mut dyn Write,
+    config: &'a Config,
+    /// Whether each open entry has had a child written yet
+    frames: Vec<bool>,
+}
+
+impl Render for Json<'_> {
+    fn node(&mut self, node: &Node, _last: bool) -> io::Result<()> {
+        match self.frames.last_mut() {
+            None => writeln!(self.out, "[")?,
+            Some(true) => writeln!(self.out, ",")?,
+            Some(has_child) => {
+                *has_child = true;
+                writeln!(self.out)?;
+            }
+        }
+
+        // The entry is left open so its contents can follow
+        let fields = serde_json::to_string(&JsonNode::new(node, self.config))?;
+        let indent = "  ".repeat(self.frames.len() + 1);
+        write!(self.out, "{}{}", indent, fields.trim_end_matches('}'))?;
+        if node.kind == Kind::Directory {
+            write!(self.out, ",\"contents\":[")?;
+        }
+        self.frames.push(false);
+        Ok(())
+    }
+
+    fn end(&mut self, node: &Node) -> io::Result<()> {
+        let has_child = self.frames.pop().unwrap_or_default();
+        if node.kind == Kind::File {
+            return write!(self.out, "}}");
+        }
+        if has_child {
+            write!(self.out, "\n{}", "  ".repeat(self.frames.len() + 1))?;
+        }
+        write!(self.out, "]}}")
+    }
+
+    fn finish(&mut self, counts: &Counts) -> io::Result<()> {
+        if !self.config.no_report {
+            let report = JsonReport {
+                kind: "report",
+                size: self.config.du.then_some(counts.bytes),
+                directories: counts.dirs,
+                files: (!self.config.dirs_only).then_some(counts.files),
+            };
+            write!(self.out, "\n,\n  {}", serde_json::to_string(&report)?)?;
+        }
+        writeln!(self.out, "\n]")
+    }
+}
+
+// --------------------------------------------------
+// XML, shaped like `tree -X`
+
+struct Xml<'a> {
+    out: &'a mut dyn Write,
+    config: &'a Config,
+    /// Whether each open element has had a child written yet
+    frames: Vec<bool>,
+}
+
+impl Render for Xml<'_> {
+    fn node(&mut self, node: &Node, _last: bool) -> io::Result<()> {
+        match self.frames.last_mut() {
+            None => write!(
+                self.out,
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tree>"
+            )?,
+            Some(has_child) => *has_child = true,
+        }
+
+        let indent = "  ".repeat(self.frames.len() + 1);
+        let columns = Columns::new(node, self.config);
+        let mut attrs = format!(" name=\"{}\"", escape(&node.name));
+        let extra = [
+            ("target", node.target.clone()),
+            ("mode", columns.mode),
+            ("prot", columns.prot),
+            ("user", columns.user),
+            ("group", columns.group),
+            ("size", columns.size.map(|size| size.to_string())),
+            ("time", columns.time),
+        ];
+        for (key, value) in extra {
+            if let Some(value) = value {
+                attrs += &format!(" {}=\"{}\"", key, escape(&value));
+            }
+        }
+
+        write!(self.out, "\n{}<{}{}>", indent, node.type_name(), attrs)?;
+        if let Some(error) = &node.error {
+            write!(self.out, "\n{}  <error>{}</error>", indent, escape(error))?;
+        }
+        self.frames.push(node.error.is_some());
+        Ok(())
+    }
+
+    fn end(&mut self, node: &Node) -> io::Result<()> {
+        if self.frames.pop().unwrap_or_default() {
+            write!(self.out, "\n{}", "  ".repeat(self.frames.len() + 1))?;
+        }
+        write!(self.out, "</{}>", node.type_name())
+    }
+
+    fn finish(&mut self, counts: &Counts) -> io::Result<()> {
+        writeln!(self.out)?;
+        if !self.config.no_report {
+            writeln!(self.out, "  <report>")?;
+            if self.config.du {
+                writeln!(self.out, "    <size>{}</size>", counts.bytes)?;
+            }
+            writeln!(self.out, "    <directories>{}</directories>", counts.dirs)?;
+            if !self.config.dirs_only {
+                writeln!(self.out, "    <files>{}</files>", counts.files)?;
+            }
+            writeln!(self.out, "  </report>")?;
+        }
+        writeln!(self.out, "</tree>")
+    }
+}
+
+// --------------------------------------------------
+
+/// Given file metadata, return a string like "drwxr-x--x"
+fn format_mode(meta: &std::fs::Metadata) -> String {
+    let file_type = if meta.is_dir() {
+        'd'
+    } else if meta.file_type().is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let mode = meta.mode();
+    let mut perms = String::from(file_type);
+
+    for each_mask in [0o400, 0o040, 0o004] {
+        perms.push(if mode & each_mask != 0 { 'r' } else { '-' });
+        perms.push(if mode & each_mask >> 1 != 0 { 'w' } else { '-' });
+        perms.push(if mode & each_mask >> 2 != 0 { 'x' } else { '-' });
+    }
+
+    perms
+}
+
+/// Format a byte count the way `tree -h` does, e.g. " 512" or "4.0K"
+fn human_size(size: u64) -> String {
+    if size < 1024 {
+        return format!("{:>4}", size);
+    }
+
+    let units = ['K', 'M', 'G', 'T', 'P', 'E'];
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < units.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if value < 9.95 {
+        format!("{:>3.1}{}", value, units[unit])
+    } else {
+        format!("{:>3.0}{}", value, units[unit])
+    }
+}
+
+/// Escape text for use in XML or HTML content and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Percent-encode a single path segment for use in a link
+fn url_encode(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded += &format!("%{:02X}", byte),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, human_size, url_encode};
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "   0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(4096), "4.0K");
+        assert_eq!(human_size(10 * 1024), " 10K");
+        assert_eq!(human_size(1536 * 1024), "1.5M");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0G");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a.txt"), "a.txt");
+        assert_eq!(escape("<a & 'b'>\""), "&lt;a &amp; &#39;b&#39;&gt;&quot;");
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(url_encode("c.txt"), "c.txt");
+        assert_eq!(url_encode("my file#1"), "my%20file%231");
+        assert_eq!(url_encode("é"), "%C3%A9");
+    }
+}
@@ -0,0 +1,522 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const PRG: &str = "treer";
+const INPUTS: &str = "tests/inputs";
+
+// --------------------------------------------------
+fn run(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_level() -> TestResult {
+    for bad in ["0", "x"] {
+        Command::cargo_bin(PRG)?
+            .args(["-L", bad, INPUTS])
+            .assert()
+            .failure()
+            .stderr(predicate::str::contains("Invalid level"));
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn missing_dir() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("tests/no-such-dir")
+        .assert()
+        .failure()
+        .stdout("tests/no-such-dir [error opening dir]\n\n0 directories, 0 files\n")
+        .stderr(predicate::str::starts_with("tests/no-such-dir: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs() -> TestResult {
+    run(&[INPUTS], "tests/expected/inputs.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_level1() -> TestResult {
+    run(&["-L", "1", INPUTS], "tests/expected/inputs.L1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_level2() -> TestResult {
+    run(&["-L", "2", INPUTS], "tests/expected/inputs.L2.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_all() -> TestResult {
+    run(&["-a", INPUTS], "tests/expected/inputs.a.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_dirs_only() -> TestResult {
+    run(&["-d", INPUTS], "tests/expected/inputs.d.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_include() -> TestResult {
+    run(&["-P", "c*|g*", INPUTS], "tests/expected/inputs.P.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_exclude() -> TestResult {
+    run(&["-I", "b|e.txt", INPUTS], "tests/expected/inputs.I.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_include_prune() -> TestResult {
+    run(
+        &["-P", "g*", "--prune", INPUTS],
+        "tests/expected/inputs.P.prune.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_json() -> TestResult {
+    run(&["-J", INPUTS], "tests/expected/inputs.J.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_xml() -> TestResult {
+    run(&["-X", "-L", "1", INPUTS], "tests/expected/inputs.X.L1.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn inputs_html() -> TestResult {
+    run(
+        &["-H", "http://example.com/docs", INPUTS],
+        "tests/expected/inputs.H.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn escapes_markup() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join("a <b> & c.txt"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-X", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"<file name="a &lt;b&gt; &amp; c.txt"></file>"#,
+        ));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-H", "/files", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"└── <a href="/files/a%20%3Cb%3E%20%26%20c.txt">a &lt;b&gt; &amp; c.txt</a><br>"#,
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_many_formats() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-J", "-X", INPUTS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_bad_pattern() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-P", "[", INPUTS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("[: "));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn prune_empty_dirs() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::create_dir_all(dir.path().join("empty/nested"))?;
+    fs::create_dir(dir.path().join("full"))?;
+    fs::write(dir.path().join("full/file.txt"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--prune", "."])
+        .assert()
+        .success()
+        .stdout(".\n└── full\n    └── file.txt\n\n1 directory, 1 file\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn gitignore() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join(".gitignore"), "*.log\nbuild/\n")?;
+    fs::create_dir_all(dir.path().join("build"))?;
+    fs::create_dir_all(dir.path().join("src"))?;
+    fs::write(dir.path().join("src/.gitignore"), "!keep.log\n")?;
+    fs::write(dir.path().join("src/keep.log"), "")?;
+    fs::write(dir.path().join("src/main.rs"), "")?;
+    fs::write(dir.path().join("debug.log"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--gitignore", "."])
+        .assert()
+        .success()
+        .stdout(".\n└── src\n    ├── keep.log\n    └── main.rs\n\n1 directory, 2 files\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn sizes() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join("small.txt"), "hello")?;
+    fs::write(dir.path().join("big.bin"), vec![0; 1536])?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-s", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("├── [       1536]  big.bin\n"))
+        .stdout(predicate::str::contains("└── [          5]  small.txt\n"));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-h", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("├── [1.5K]  big.bin\n"))
+        .stdout(predicate::str::contains("└── [   5]  small.txt\n"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn disk_usage() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::create_dir(dir.path().join("sub"))?;
+    fs::write(dir.path().join("sub/a.txt"), "abc")?;
+    fs::write(dir.path().join("b.txt"), "de")?;
+
+    let sub_size = fs::metadata(dir.path().join("sub"))?.len() + 3;
+    let root_size = fs::metadata(dir.path())?.len() + sub_size + 2;
+    let expected = format!(
+        "[{:>11}]  .\n├── [{:>11}]  b.txt\n└── [{:>11}]  sub\n    \
+         └── [{:>11}]  a.txt\n\n{} used in 1 directory, 2 files\n",
+        root_size, 2, sub_size, 3, root_size
+    );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--du", "."])
+        .assert()
+        .success()
+        .stdout(expected);
+
+    // Directories beyond -L still count towards their parent's total
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--du", "-L", "1", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "[{:>11}]  sub\n",
+            sub_size
+        )))
+        .stdout(predicate::str::ends_with(format!(
+            "{} used in 1 directory, 1 file\n",
+            root_size
+        )));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn metadata_columns() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::write(dir.path().join("file.txt"), "")?;
+
+    let pattern = r"(?m)^└── \[-[r-][w-][x-][r-][w-][x-][r-][w-][x-] \S+ +\S+ +0 \w{3} \d{2} \d{2}:\d{2}\]  file\.txt$";
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-pugsD", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(pattern)?)
+        .stdout(predicate::str::starts_with("[d"));
+    Ok(())
+}
+
+// --------------------------------------------------
+fn sort_fixture() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    let now = SystemTime::now();
+    for (name, size, age) in [("file10", 1, 30), ("file9", 300, 10), ("file1", 20, 20)] {
+        let file = fs::File::create(dir.path().join(name))?;
+        file.set_len(size)?;
+        file.set_modified(now - Duration::from_secs(age))?;
+    }
+    fs::create_dir(dir.path().join("dir"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn sorts() -> TestResult {
+    let dir = sort_fixture()?;
+    let tests = [
+        (vec![], "dir file1 file10 file9"),
+        (vec!["--sort=version"], "dir file1 file9 file10"),
+        (vec!["--sort", "version", "-r"], "file10 file9 file1 dir"),
+        (vec!["--sort=mtime", "-I", "dir"], "file10 file1 file9"),
+        (vec!["--sort=size", "-I", "dir"], "file9 file1 file10"),
+        (vec!["--sort=size", "-r", "-I", "dir"], "file10 file1 file9"),
+        (vec!["-r", "--dirsfirst"], "dir file9 file10 file1"),
+        (vec!["-r", "--filesfirst"], "file9 file10 file1 dir"),
+    ];
+
+    for (args, expected) in tests {
+        let output = Command::cargo_bin(PRG)?
+            .current_dir(dir.path())
+            .args(&args)
+            .arg(".")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        let names: Vec<_> = stdout
+            .lines()
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .map(|line| line.rsplit(' ').next().unwrap())
+            .collect();
+        assert_eq!(names.join(" "), expected, "args {:?}", args);
+    }
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn unsorted_lists_everything() -> TestResult {
+    let dir = sort_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-U", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("file9"))
+        .stdout(predicate::str::ends_with("\n1 directory, 3 files\n"));
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-U", "--sort=name", "."])
+        .assert()
+        .failure();
+    Ok(())
+}
+
+// --------------------------------------------------
+fn link_fixture() -> Result<TempDir, Box<dyn std::error::Error>> {
+    let dir = TempDir::new()?;
+    fs::create_dir_all(dir.path().join("d/sub"))?;
+    fs::write(dir.path().join("d/f.txt"), "")?;
+    symlink("nowhere", dir.path().join("broken"))?;
+    symlink("d", dir.path().join("dlink"))?;
+    symlink("d/f.txt", dir.path().join("flink"))?;
+    symlink("../..", dir.path().join("d/sub/up"))?;
+    Ok(dir)
+}
+
+// --------------------------------------------------
+#[test]
+fn symlinks() -> TestResult {
+    let dir = link_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .arg(".")
+        .assert()
+        .success()
+        .stdout(
+            ".\n\
+             ├── broken -> nowhere  [broken link]\n\
+             ├── d\n\
+             │   ├── f.txt\n\
+             │   └── sub\n\
+             │       └── up -> ../..\n\
+             ├── dlink -> d\n\
+             └── flink -> d/f.txt\n\
+             \n\
+             4 directories, 3 files\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn follow_symlinks() -> TestResult {
+    let dir = link_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-l", "."])
+        .assert()
+        .success()
+        .stdout(
+            ".\n\
+             ├── broken -> nowhere  [broken link]\n\
+             ├── d\n\
+             │   ├── f.txt\n\
+             │   └── sub\n\
+             │       └── up -> ../..  [recursive, not followed]\n\
+             ├── dlink -> d\n\
+             │   ├── f.txt\n\
+             │   └── sub\n\
+             │       └── up -> ../..  [recursive, not followed]\n\
+             └── flink -> d/f.txt\n\
+             \n\
+             6 directories, 4 files\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn symlinks_json() -> TestResult {
+    let dir = link_fixture()?;
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["-J", "-L", "1", "."])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            r#"    {"type":"link","name":"dlink","target":"d","contents":[]},"#,
+        ))
+        .stdout(predicate::str::contains(
+            r#"    {"type":"link","name":"broken","target":"nowhere","error":"broken link"},"#,
+        ));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn file_limit() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::create_dir(dir.path().join("many"))?;
+    for i in 0..5 {
+        fs::write(dir.path().join(format!("many/{}.txt", i)), "")?;
+    }
+    fs::create_dir(dir.path().join("few"))?;
+    fs::write(dir.path().join("few/a.txt"), "")?;
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--filelimit", "4", "."])
+        .assert()
+        .success()
+        .stdout(
+            ".\n\
+             ├── few\n\
+             │   └── a.txt\n\
+             └── many  [5 entries exceeds filelimit, not opened]\n\
+             \n\
+             2 directories, 1 file\n",
+        );
+
+    Command::cargo_bin(PRG)?
+        .args(["--filelimit", "0", INPUTS])
+        .assert()
+        .failure()
+        .stderr("Invalid file limit \"0\"\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn no_report() -> TestResult {
+    let expected = fs::read_to_string("tests/expected/inputs.out")?;
+    let tree = expected.split("\n\n").next().unwrap();
+    Command::cargo_bin(PRG)?
+        .args(["--noreport", INPUTS])
+        .assert()
+        .success()
+        .stdout(format!("{}\n", tree));
+
+    Command::cargo_bin(PRG)?
+        .args(["--noreport", "-J", "-L", "1", "-P", "e*", INPUTS])
+        .assert()
+        .success()
+        .stdout(
+            "[\n  {\"type\":\"directory\",\"name\":\"tests/inputs\",\"contents\":[\n    \
+             {\"type\":\"directory\",\"name\":\"a\",\"contents\":[]},\n    \
+             {\"type\":\"file\",\"name\":\"e.txt\"},\n    \
+             {\"type\":\"directory\",\"name\":\"f\",\"contents\":[]}\n  ]}\n]\n",
+        );
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn disk_usage_nested_prune() -> TestResult {
+    let dir = TempDir::new()?;
+    fs::create_dir_all(dir.path().join("a/b/c"))?;
+    fs::create_dir_all(dir.path().join("a/empty/nested"))?;
+    fs::write(dir.path().join("a/b/c/x.txt"), "abcd")?;
+    fs::write(dir.path().join("a/b/y.txt"), "ef")?;
+
+    let len = |path: &str| fs::metadata(dir.path().join(path)).map(|meta| meta.len());
+    let c_size = len("a/b/c")? + 4;
+    let b_size = len("a/b")? + c_size + 2;
+    let a_size = len("a")? + b_size;
+    let root_size = len(".")? + a_size;
+    let expected = format!(
+        "[{:>11}]  .\n└── [{:>11}]  a\n    └── [{:>11}]  b\n        \
+         ├── [{:>11}]  c\n        │   └── [{:>11}]  x.txt\n        \
+         └── [{:>11}]  y.txt\n\n{} used in 3 directories, 2 files\n",
+        root_size, a_size, b_size, c_size, 4, 2, root_size
+    );
+
+    Command::cargo_bin(PRG)?
+        .current_dir(dir.path())
+        .args(["--du", "--prune", "."])
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
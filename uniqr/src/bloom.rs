@@ -0,0 +1,115 @@
+use std::collections::hash_map::DefaultHasher;
+use std::f64::consts::LN_2;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u64,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// Build a filter that uses at most `max_bytes` of memory, with enough
+    /// hash functions to keep false positives near `fp_rate`
+    pub fn new(max_bytes: usize, fp_rate: f64) -> Self {
+        let words = (max_bytes / 8).max(1);
+        let num_bits = words as u64 * 64;
+        let num_hashes = (-fp_rate.log2()).ceil().max(1.0) as u64;
+        let capacity = (num_bits as f64 * LN_2 * LN_2 / -fp_rate.ln()) as usize;
+
+        BloomFilter {
+            bits: vec![0; words],
+            num_bits,
+            num_hashes,
+            capacity,
+            len: 0,
+        }
+    }
+
+    /// How many distinct items fit before the false-positive rate is exceeded
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Add an item, returning `true` if it was definitely not present before
+    pub fn insert<T: Hash + ?Sized>(&mut self, item: &T) -> bool {
+        let (h1, h2) = hash_pair(item);
+        let mut is_new = false;
+
+        for i in 0..self.num_hashes {
+            let bit = h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits;
+            let (word, mask) = ((bit / 64) as usize, 1 << (bit % 64));
+            if self.bits[word] & mask == 0 {
+                self.bits[word] |= mask;
+                is_new = true;
+            }
+        }
+
+        if is_new {
+            self.len += 1;
+        }
+        is_new
+    }
+}
+
+/// Two independent hashes for double hashing; the second is forced odd so
+/// that it never collapses every probe onto the same bit
+fn hash_pair<T: Hash + ?Sized>(item: &T) -> (u64, u64) {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    let h1 = hasher.finish();
+
+    let mut hasher = DefaultHasher::new();
+    h1.hash(&mut hasher);
+    item.hash(&mut hasher);
+    let h2 = hasher.finish() | 1;
+
+    (h1, h2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BloomFilter;
+
+    #[test]
+    fn test_insert() {
+        let mut filter = BloomFilter::new(1024, 0.01);
+        assert!(filter.insert("a"));
+        assert!(filter.insert("b"));
+        assert!(!filter.insert("a"));
+        assert!(!filter.insert("b"));
+        assert_eq!(filter.len(), 2);
+    }
+
+    #[test]
+    fn test_sizing() {
+        let filter = BloomFilter::new(1024, 0.01);
+        assert_eq!(filter.num_bits, 8192);
+        assert_eq!(filter.num_hashes, 7);
+        assert_eq!(filter.capacity(), 854);
+
+        let filter = BloomFilter::new(3, 0.5);
+        assert_eq!(filter.num_bits, 64);
+        assert_eq!(filter.num_hashes, 1);
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        let mut filter = BloomFilter::new(1024, 0.01);
+        for i in 0..filter.capacity() {
+            filter.insert(&i);
+        }
+
+        let false_positives = (10_000..20_000)
+            .filter(|i| !filter.clone().insert(i))
+            .count();
+        assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+}
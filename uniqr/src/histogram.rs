@@ -0,0 +1,163 @@
+use regex::bytes::Regex;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const BAR_WIDTH: usize = 40;
+
+#[derive(Debug, Clone)]
+pub enum Selector {
+    Line,
+    Field(usize),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    Count,
+    Key,
+}
+
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    pub selector: Selector,
+    pub sort_by: SortBy,
+    pub top: Option<usize>,
+    pub percent: bool,
+    pub bar: bool,
+}
+
+impl Selector {
+    /// Pull the key to count out of a line, or `None` if the line has none.
+    /// A regex selector yields its first capture group, or the whole match
+    /// when the pattern has no groups
+    pub fn select<'a>(&self, line: &'a [u8]) -> Option<&'a [u8]> {
+        match self {
+            Selector::Line => Some(line),
+            Selector::Field(n) => line
+                .split(u8::is_ascii_whitespace)
+                .filter(|field| !field.is_empty())
+                .nth(n - 1),
+            Selector::Regex(re) => re
+                .captures(line)
+                .and_then(|caps| caps.get(1).or_else(|| caps.get(0)))
+                .map(|m| m.as_bytes()),
+        }
+    }
+}
+
+impl Histogram {
+    /// Write one row per key, each ending with `delimiter`, keeping the
+    /// bytes of the keys as they were read
+    pub fn write(
+        &self,
+        counts: HashMap<Vec<u8>, usize>,
+        output: &mut dyn Write,
+        delimiter: u8,
+    ) -> io::Result<()> {
+        let total: usize = counts.values().sum();
+        let mut rows: Vec<_> = counts.into_iter().collect();
+        match self.sort_by {
+            SortBy::Count => rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))),
+            SortBy::Key => rows.sort(),
+        }
+        if let Some(top) = self.top {
+            rows.truncate(top);
+        }
+
+        let max = rows.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        for (key, count) in rows {
+            write!(output, "{:>4}", count)?;
+            if self.percent {
+                write!(output, " {:>6.2}%", 100.0 * count as f64 / total as f64)?;
+            }
+            if self.bar {
+                let len = (count * BAR_WIDTH).div_ceil(max);
+                write!(output, " {:<width$}", "#".repeat(len), width = BAR_WIDTH)?;
+            }
+            output.write_all(b" ")?;
+            output.write_all(&key)?;
+            output.write_all(&[delimiter])?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Histogram, Selector, SortBy};
+    use regex::bytes::Regex;
+    use std::collections::HashMap;
+
+    fn counts() -> HashMap<Vec<u8>, usize> {
+        [("b", 1), ("a", 3), ("c", 1)]
+            .iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), *v))
+            .collect()
+    }
+
+    fn format(histogram: &Histogram, delimiter: u8) -> String {
+        let mut output = vec![];
+        histogram.write(counts(), &mut output, delimiter).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(Selector::Line.select(b"a b"), Some(&b"a b"[..]));
+        assert_eq!(Selector::Field(2).select(b" a  b c"), Some(&b"b"[..]));
+        assert_eq!(Selector::Field(2).select(b"a \xff"), Some(&b"\xff"[..]));
+        assert_eq!(Selector::Field(4).select(b"a b c"), None);
+
+        let re = Regex::new(r"user=(\w+)").unwrap();
+        assert_eq!(
+            Selector::Regex(re).select(b"id=1 user=ken"),
+            Some(&b"ken"[..])
+        );
+        let re = Regex::new(r"\d+").unwrap();
+        assert_eq!(
+            Selector::Regex(re.clone()).select(b"id=12"),
+            Some(&b"12"[..])
+        );
+        assert_eq!(Selector::Regex(re).select(b"none"), None);
+    }
+
+    #[test]
+    fn test_format_by_count() {
+        let histogram = Histogram {
+            selector: Selector::Line,
+            sort_by: SortBy::Count,
+            top: None,
+            percent: false,
+            bar: false,
+        };
+        assert_eq!(format(&histogram, b'\n'), "   3 a\n   1 b\n   1 c\n");
+        assert_eq!(format(&histogram, b'\0'), "   3 a\0   1 b\0   1 c\0");
+
+        let histogram = Histogram {
+            top: Some(2),
+            percent: true,
+            ..histogram
+        };
+        assert_eq!(
+            format(&histogram, b'\n'),
+            "   3  60.00% a\n   1  20.00% b\n"
+        );
+    }
+
+    #[test]
+    fn test_format_by_key_with_bar() {
+        let histogram = Histogram {
+            selector: Selector::Line,
+            sort_by: SortBy::Key,
+            top: None,
+            percent: false,
+            bar: true,
+        };
+        let res = format(&histogram, b'\n');
+        let lines: Vec<_> = res.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], format!("   3 {} a", "#".repeat(40)));
+        assert_eq!(lines[1], format!("   1 {:<40} b", "#".repeat(14)));
+        assert_eq!(lines[2], format!("   1 {:<40} c", "#".repeat(14)));
+    }
+}
@@ -1,15 +1,55 @@
+mod bloom;
+mod histogram;
+
+use bloom::BloomFilter;
 use clap::{App, Arg};
+use histogram::{Histogram, Selector, SortBy};
+use regex::bytes::Regex;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    in_file: String,
+    in_files: Vec<String>,
     out_file: Option<String>,
     count: bool,
+    repeated: bool,
+    all_repeated: bool,
+    unique: bool,
+    ignore_case: bool,
+    skip_fields: usize,
+    skip_chars: usize,
+    check_chars: Option<usize>,
+    global: bool,
+    approx: bool,
+    fp_rate: f64,
+    max_memory: usize,
+    histogram: Option<Histogram>,
+    zero_terminated: bool,
+    group: Option<GroupMethod>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupMethod {
+    Prepend,
+    Append,
+    Separate,
+    Both,
+}
+
+impl Config {
+    fn delimiter(&self) -> u8 {
+        if self.zero_terminated {
+            b'\0'
+        } else {
+            b'\n'
+        }
+    }
 }
 
 pub fn get_args() -> MyResult<Config> {
@@ -25,14 +65,181 @@ pub fn get_args() -> MyResult<Config> {
                 .takes_value(false),
         )
         .arg(
-            Arg::with_name("in_file")
-                .value_name("IN_FILE")
-                .help("Input file")
+            Arg::with_name("repeated")
+                .short("d")
+                .long("repeated")
+                .help("Only print duplicate lines, one for each group"),
+        )
+        .arg(
+            Arg::with_name("all_repeated")
+                .short("D")
+                .long("all-repeated")
+                .help("Print all duplicate lines")
+                .conflicts_with("count"),
+        )
+        .arg(
+            Arg::with_name("unique")
+                .short("u")
+                .long("unique")
+                .help("Only print unique lines"),
+        )
+        .arg(
+            Arg::with_name("ignore_case")
+                .short("i")
+                .long("ignore-case")
+                .help("Ignore differences in case when comparing"),
+        )
+        .arg(
+            Arg::with_name("skip_fields")
+                .short("f")
+                .long("skip-fields")
+                .value_name("N")
+                .help("Avoid comparing the first N fields")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("skip_chars")
+                .short("s")
+                .long("skip-chars")
+                .value_name("N")
+                .help("Avoid comparing the first N characters")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("check_chars")
+                .short("w")
+                .long("check-chars")
+                .value_name("N")
+                .help("Compare no more than N characters in lines")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("zero_terminated")
+                .short("z")
+                .long("zero-terminated")
+                .help("Line delimiter is NUL, not newline"),
+        )
+        .arg(
+            Arg::with_name("global")
+                .long("global")
+                .help("Remove duplicates anywhere in the input, keeping the first"),
+        )
+        .arg(
+            Arg::with_name("approx")
+                .long("approx")
+                .help("Like --global, but track seen lines with a Bloom filter")
+                .conflicts_with_all(&["count", "repeated", "all_repeated", "unique"]),
+        )
+        .arg(
+            Arg::with_name("fp_rate")
+                .long("fp-rate")
+                .value_name("RATE")
+                .help("False-positive rate for --approx")
+                .takes_value(true)
+                .default_value("0.01"),
+        )
+        .arg(
+            Arg::with_name("max_memory")
+                .long("max-memory")
+                .value_name("SIZE")
+                .help("Memory used by --approx, e.g. 512K, 64M, 1G")
+                .takes_value(true)
+                .default_value("64M"),
+        )
+        .arg(
+            Arg::with_name("histogram")
+                .long("histogram")
+                .help("Count every distinct line or key across the whole input")
+                .conflicts_with_all(&[
+                    "count",
+                    "repeated",
+                    "all_repeated",
+                    "unique",
+                    "global",
+                    "approx",
+                ]),
+        )
+        .arg(
+            Arg::with_name("field")
+                .long("field")
+                .value_name("N")
+                .help("Histogram key is the Nth whitespace-separated field")
                 .takes_value(true)
+                .requires("histogram")
+                .conflicts_with("regex"),
+        )
+        .arg(
+            Arg::with_name("regex")
+                .long("regex")
+                .value_name("PATTERN")
+                .help("Histogram key is the first capture group (or match) of PATTERN")
+                .takes_value(true)
+                .requires("histogram"),
+        )
+        .arg(
+            Arg::with_name("top")
+                .long("top")
+                .value_name("N")
+                .help("Only show the first N histogram rows")
+                .takes_value(true)
+                .requires("histogram"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .long("sort")
+                .value_name("ORDER")
+                .help("Sort histogram rows by count or key [default: count]")
+                .possible_values(&["count", "key"])
+                .requires("histogram"),
+        )
+        .arg(
+            Arg::with_name("percent")
+                .long("percent")
+                .help("Add a percentage column to the histogram")
+                .requires("histogram"),
+        )
+        .arg(
+            Arg::with_name("bar")
+                .long("bar")
+                .help("Add a bar chart column to the histogram")
+                .requires("histogram"),
+        )
+        .arg(
+            Arg::with_name("group")
+                .long("group")
+                .value_name("METHOD")
+                .help("Show all lines, separating groups with an empty line")
+                .takes_value(true)
+                .min_values(0)
+                .require_equals(true)
+                .possible_values(&["prepend", "append", "separate", "both"])
+                .conflicts_with_all(&[
+                    "count",
+                    "repeated",
+                    "all_repeated",
+                    "unique",
+                    "global",
+                    "approx",
+                    "histogram",
+                ]),
+        )
+        .arg(
+            Arg::with_name("files")
+                .value_name("FILE")
+                .help("Input file, then output file [IN_FILE [OUT_FILE]]")
+                .takes_value(true)
+                .multiple(true)
                 .default_value("-"),
         )
+        .arg(
+            Arg::with_name("concat")
+                .long("concat")
+                .help("Read every FILE as input, as one stream"),
+        )
         .arg(
             Arg::with_name("out_file")
+                .short("o")
+                .long("output")
                 .value_name("OUT_FILE")
                 .help("Output file")
                 .takes_value(true),
@@ -40,45 +247,371 @@ pub fn get_args() -> MyResult<Config> {
         .get_matches();
 
     let count = matches.is_present("count");
-    let in_file = matches.value_of("in_file").unwrap().to_string();
-    let out_file = matches.value_of("out_file").map(String::from);
+    let mut in_files = matches.values_of_lossy("files").unwrap();
+    let mut out_file = matches.value_of("out_file").map(String::from);
+    // As with uniq, a second file is where the output goes, unless every
+    // file is an input
+    if !matches.is_present("concat") {
+        if in_files.len() > 2 {
+            return Err(From::from(format!(
+                "extra operand '{}', use --concat to read several inputs",
+                in_files[2]
+            )));
+        }
+        if in_files.len() == 2 {
+            if out_file.is_some() {
+                return Err(From::from("OUT_FILE cannot be given with --output"));
+            }
+            out_file = in_files.pop();
+        }
+    }
+    let skip_fields = matches
+        .value_of("skip_fields")
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of fields to skip -- {}", e))?
+        .unwrap_or(0);
+    let skip_chars = matches
+        .value_of("skip_chars")
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of bytes to skip -- {}", e))?
+        .unwrap_or(0);
+    let check_chars = matches
+        .value_of("check_chars")
+        .map(parse_count)
+        .transpose()
+        .map_err(|e| format!("invalid number of bytes to compare -- {}", e))?;
+    let fp_rate = matches
+        .value_of("fp_rate")
+        .map(parse_rate)
+        .transpose()
+        .map_err(|e| format!("invalid false-positive rate -- {}", e))?
+        .unwrap();
+    let max_memory = matches
+        .value_of("max_memory")
+        .map(parse_size)
+        .transpose()
+        .map_err(|e| format!("invalid memory size -- {}", e))?
+        .unwrap();
+
+    let histogram = if matches.is_present("histogram") {
+        let field = matches
+            .value_of("field")
+            .map(parse_positive_int)
+            .transpose()
+            .map_err(|e| format!("invalid field number -- {}", e))?;
+        let regex = matches
+            .value_of("regex")
+            .map(|re| Regex::new(re).map_err(|_| format!("Invalid --regex \"{}\"", re)))
+            .transpose()?;
+        let selector = match (field, regex) {
+            (Some(n), _) => Selector::Field(n),
+            (_, Some(re)) => Selector::Regex(re),
+            _ => Selector::Line,
+        };
+        let top = matches
+            .value_of("top")
+            .map(parse_positive_int)
+            .transpose()
+            .map_err(|e| format!("invalid number of rows -- {}", e))?;
+        let sort_by = match matches.value_of("sort") {
+            Some("key") => SortBy::Key,
+            _ => SortBy::Count,
+        };
+
+        Some(Histogram {
+            selector,
+            sort_by,
+            top,
+            percent: matches.is_present("percent"),
+            bar: matches.is_present("bar"),
+        })
+    } else {
+        None
+    };
 
     Ok(Config {
         count,
-        in_file,
+        in_files,
         out_file,
+        repeated: matches.is_present("repeated"),
+        all_repeated: matches.is_present("all_repeated"),
+        unique: matches.is_present("unique"),
+        ignore_case: matches.is_present("ignore_case"),
+        skip_fields,
+        skip_chars,
+        check_chars,
+        global: matches.is_present("global"),
+        approx: matches.is_present("approx"),
+        fp_rate,
+        max_memory,
+        histogram,
+        zero_terminated: matches.is_present("zero_terminated"),
+        group: if matches.is_present("group") {
+            Some(match matches.value_of("group") {
+                Some("prepend") => GroupMethod::Prepend,
+                Some("append") => GroupMethod::Append,
+                Some("both") => GroupMethod::Both,
+                _ => GroupMethod::Separate,
+            })
+        } else {
+            None
+        },
     })
 }
 
+fn parse_count(val: &str) -> MyResult<usize> {
+    val.parse::<usize>().map_err(|_| From::from(val))
+}
+
+fn parse_positive_int(val: &str) -> MyResult<usize> {
+    match val.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(From::from(val)),
+    }
+}
+
+fn parse_rate(val: &str) -> MyResult<f64> {
+    match val.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && rate < 1.0 => Ok(rate),
+        _ => Err(From::from(val)),
+    }
+}
+
+fn parse_size(val: &str) -> MyResult<usize> {
+    let (num, unit) = match val.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => val.split_at(i),
+        None => (val, ""),
+    };
+    let multiplier = match unit {
+        "" => 1,
+        "K" | "k" => 1024,
+        "M" | "m" => 1024 * 1024,
+        "G" | "g" => 1024 * 1024 * 1024,
+        _ => return Err(From::from(val)),
+    };
+    match num.parse::<usize>().map(|n| n.checked_mul(multiplier)) {
+        Ok(Some(size)) if size > 0 => Ok(size),
+        _ => Err(From::from(val)),
+    }
+}
+
 pub fn run(config: Config) -> MyResult<()> {
-    // println!("{:?}", config);
-    let mut file = open(&config.in_file).map_err(|e| format!("{}: {}", config.in_file, e))?;
-    let mut output_buffer = create(config.out_file).map_err(|e| format!("{}", e))?;
-    let mut line = String::new();
-    let mut previous = String::from("");
+    let mut file = open_all(&config.in_files, config.delimiter())?;
+    let mut output_buffer = create(config.out_file.clone()).map_err(|e| format!("{}", e))?;
+    uniq(&mut file, &mut output_buffer, &config)?;
+    output_buffer.flush()?;
+    Ok(())
+}
+
+/// Filter the records of `input` into `output` according to `config`
+pub fn uniq(input: &mut dyn BufRead, output: &mut dyn Write, config: &Config) -> MyResult<()> {
+    if let Some(histogram) = &config.histogram {
+        run_histogram(input, output, config, histogram)
+    } else if config.approx {
+        run_approx(input, output, config)
+    } else if config.global {
+        run_global(input, output, config)
+    } else {
+        run_adjacent(input, output, config)
+    }
+}
+
+fn run_adjacent(file: &mut dyn BufRead, output: &mut dyn Write, config: &Config) -> MyResult<()> {
+    let keep_duplicates = config.all_repeated || config.group.is_some();
+    let mut record = vec![];
+    let mut previous = vec![];
+    let mut duplicates = vec![];
     let mut count = 0;
+    let mut groups = 0;
 
-    loop {
-        let bytes = file.read_line(&mut line)?;
-        if bytes == 0 {
-            write_output(&mut output_buffer, previous, count, config.count)?;
-            break;
-        }
-        if previous == "" {
-            previous = line.clone();
-            count += 1;
-        } else if previous.trim() != line.trim() {
-            write_output(&mut output_buffer, previous, count, config.count)?;
-            previous = line.clone();
-            count = 1;
-        } else {
-            count += 1;
+    while read_record(file, &mut record, config.delimiter())? {
+        if count > 0 && compare_key(&previous, config) != compare_key(&record, config) {
+            write_group(output, &previous, &duplicates, count, config, groups)?;
+            duplicates.clear();
+            count = 0;
+            groups += 1;
+        }
+        if count == 0 {
+            previous = record.clone();
+        } else if keep_duplicates {
+            duplicates.push(record.clone());
+        }
+        count += 1;
+    }
+    if count > 0 {
+        write_group(output, &previous, &duplicates, count, config, groups)?;
+        if config.group == Some(GroupMethod::Both) {
+            output.write_all(&[config.delimiter()])?;
+        }
+    }
+    Ok(())
+}
+
+/// Write one group of equal records, surrounded by the empty records
+/// that `--group` asks for; `index` is the number of groups already written
+fn write_group(
+    output: &mut dyn Write,
+    previous: &[u8],
+    duplicates: &[Vec<u8>],
+    count: usize,
+    config: &Config,
+    index: usize,
+) -> MyResult<()> {
+    let separator = [config.delimiter()];
+    match config.group {
+        Some(GroupMethod::Prepend) | Some(GroupMethod::Both) => output.write_all(&separator)?,
+        Some(GroupMethod::Separate) if index > 0 => output.write_all(&separator)?,
+        _ => {}
+    }
+    write_output(output, previous, duplicates, count, config)?;
+    if config.group == Some(GroupMethod::Append) {
+        output.write_all(&separator)?;
+    }
+    Ok(())
+}
+
+fn run_global(file: &mut dyn BufRead, output: &mut dyn Write, config: &Config) -> MyResult<()> {
+    let keep_groups = config.count || config.repeated || config.all_repeated || config.unique;
+    let mut seen: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut groups: Vec<(Vec<u8>, Vec<Vec<u8>>, usize)> = vec![];
+    let mut record = vec![];
+
+    while read_record(file, &mut record, config.delimiter())? {
+        let key = compare_key(&record, config).into_owned();
+        match seen.get(&key) {
+            Some(&i) if keep_groups => {
+                let (_, duplicates, count) = &mut groups[i];
+                if config.all_repeated {
+                    duplicates.push(record.clone());
+                }
+                *count += 1;
+            }
+            Some(_) => {}
+            None if keep_groups => {
+                seen.insert(key, groups.len());
+                groups.push((record.clone(), vec![], 1));
+            }
+            None => {
+                seen.insert(key, 0);
+                write_output(output, &record, &[], 1, config)?;
+            }
+        }
+    }
+
+    for (first, duplicates, count) in &groups {
+        write_output(output, first, duplicates, *count, config)?;
+    }
+    Ok(())
+}
+
+fn run_approx(file: &mut dyn BufRead, output: &mut dyn Write, config: &Config) -> MyResult<()> {
+    let mut filter = BloomFilter::new(config.max_memory, config.fp_rate);
+    let mut record = vec![];
+    let mut warned = false;
+
+    while read_record(file, &mut record, config.delimiter())? {
+        if filter.insert(&compare_key(&record, config)) {
+            write_output(output, &record, &[], 1, config)?;
+            if !warned && filter.len() > filter.capacity() {
+                eprintln!(
+                    "uniqr: more than {} distinct lines, false-positive rate now exceeds {}",
+                    filter.capacity(),
+                    config.fp_rate
+                );
+                warned = true;
+            }
         }
-        line.clear();
     }
     Ok(())
 }
 
+fn run_histogram(
+    file: &mut dyn BufRead,
+    output: &mut dyn Write,
+    config: &Config,
+    histogram: &Histogram,
+) -> MyResult<()> {
+    let mut counts: HashMap<Vec<u8>, usize> = HashMap::new();
+    let mut record = vec![];
+
+    while read_record(file, &mut record, config.delimiter())? {
+        if let Some(selected) = histogram.selector.select(&record) {
+            // -f, -s, -w and -i narrow the key the way they narrow what
+            // uniq compares
+            let key = compare_key(selected, config);
+            *counts.entry(key.into_owned()).or_insert(0) += 1;
+        }
+    }
+
+    histogram.write(counts, output, config.delimiter())?;
+    Ok(())
+}
+
+/// Read the next record into `record` without its delimiter, returning
+/// `false` at the end of the input
+fn read_record(file: &mut dyn BufRead, record: &mut Vec<u8>, delimiter: u8) -> MyResult<bool> {
+    record.clear();
+    if file.read_until(delimiter, record)? == 0 {
+        return Ok(false);
+    }
+    if record.last() == Some(&delimiter) {
+        record.pop();
+    }
+    Ok(true)
+}
+
+/// Return the part of a record that is compared against its neighbours,
+/// after skipping fields and characters and limiting its length
+fn compare_key<'a>(record: &'a [u8], config: &Config) -> Cow<'a, [u8]> {
+    let is_blank = |b: &u8| *b == b' ' || *b == b'\t';
+    let mut key = record;
+
+    for _ in 0..config.skip_fields {
+        let start = key.iter().position(|b| !is_blank(b)).unwrap_or(key.len());
+        key = &key[start..];
+        let end = key.iter().position(is_blank).unwrap_or(key.len());
+        key = &key[end..];
+    }
+
+    key = &key[char_offset(key, config.skip_chars)..];
+
+    if let Some(n) = config.check_chars {
+        key = &key[..char_offset(key, n)];
+    }
+
+    if !config.ignore_case {
+        Cow::Borrowed(key)
+    } else if let Ok(text) = std::str::from_utf8(key) {
+        Cow::Owned(text.to_lowercase().into_bytes())
+    } else {
+        Cow::Owned(key.to_ascii_lowercase())
+    }
+}
+
+/// Byte offset of the `n`th character in `bytes`, where every byte of an
+/// invalid UTF-8 sequence counts as a character of its own
+fn char_offset(bytes: &[u8], n: usize) -> usize {
+    let mut offset = 0;
+    let mut chars = 0;
+    for chunk in bytes.utf8_chunks() {
+        let starts = chunk
+            .valid()
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain(chunk.valid().len()..chunk.valid().len() + chunk.invalid().len());
+        for i in starts {
+            if chars == n {
+                return offset + i;
+            }
+            chars += 1;
+        }
+        offset += chunk.valid().len() + chunk.invalid().len();
+    }
+    bytes.len()
+}
+
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     match filename {
         "-" => Ok(Box::new(BufReader::new(io::stdin()))),
@@ -86,67 +619,290 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
+/// Open every input up front and chain them into a single stream, ending
+/// each one with a delimiter so records never run across files
+fn open_all(filenames: &[String], delimiter: u8) -> MyResult<Box<dyn BufRead>> {
+    if let [filename] = filenames {
+        return open(filename).map_err(|e| From::from(format!("{}: {}", filename, e)));
+    }
+
+    let mut reader: Box<dyn Read> = Box::new(io::empty());
+    for filename in filenames {
+        let file = open(filename).map_err(|e| format!("{}: {}", filename, e))?;
+        reader = Box::new(reader.chain(Terminated::new(file, delimiter)));
+    }
+    Ok(Box::new(BufReader::new(reader)))
+}
+
+/// A reader that adds a final delimiter to input that lacks one
+struct Terminated<R> {
+    inner: R,
+    delimiter: u8,
+    last: Option<u8>,
+    done: bool,
+}
+
+impl<R: Read> Terminated<R> {
+    fn new(inner: R, delimiter: u8) -> Self {
+        Terminated {
+            inner,
+            delimiter,
+            last: None,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for Terminated<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        let bytes = self.inner.read(buf)?;
+        if bytes > 0 {
+            self.last = Some(buf[bytes - 1]);
+            return Ok(bytes);
+        }
+
+        self.done = true;
+        match self.last {
+            Some(last) if last != self.delimiter => {
+                buf[0] = self.delimiter;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
 fn create(out_file: Option<String>) -> MyResult<Box<dyn Write>> {
     match out_file {
-        Some(filename) => Ok(Box::new(File::create(filename)?)),
-        None => Ok(Box::new(io::stdout())),
+        Some(filename) => Ok(Box::new(BufWriter::new(File::create(filename)?))),
+        None => Ok(Box::new(BufWriter::new(io::stdout()))),
     }
 }
 
 fn write_output(
-    buffer: &mut Box<dyn Write>,
-    previous: String,
+    buffer: &mut dyn Write,
+    previous: &[u8],
+    duplicates: &[Vec<u8>],
     count: usize,
-    print_count: bool,
+    config: &Config,
 ) -> MyResult<()> {
-    if count != 0 {
-        if print_count {
-            buffer.write_fmt(format_args!("{:>4} {}", count, previous))?;
-        } else {
-            buffer.write_fmt(format_args!("{}", previous))?;
-        }
+    if count == 0
+        || (count == 1 && (config.repeated || config.all_repeated))
+        || (count > 1 && config.unique)
+    {
+        return Ok(());
+    }
+
+    if config.count {
+        buffer.write_fmt(format_args!("{:>4} ", count))?;
+    }
+    for record in std::iter::once(previous).chain(duplicates.iter().map(Vec::as_slice)) {
+        buffer.write_all(record)?;
+        buffer.write_all(&[config.delimiter()])?;
     }
     Ok(())
 }
 
-pub fn run_example(config: Config) -> MyResult<()> {
-    let mut file = open(&config.in_file).map_err(|e| format!("{}: {}", config.in_file, e))?;
-    let mut out_file: Box<dyn Write> = match &config.out_file {
-        Some(output_path) => Box::new(File::create(output_path)?),
-        None => Box::new(io::stdout()),
-    };
-    let mut line = String::new();
-    let mut previous = String::new();
-    let mut count: u64 = 0;
-
-    let mut print = |count: u64, text: &str| -> MyResult<()> {
-        if count > 0 {
-            if config.count {
-                write!(out_file, "{:>4} {}", count, text)?;
-            } else {
-                write!(out_file, "{}", text)?;
-            }
-        }
-        Ok(())
+#[cfg(test)]
+mod tests {
+    use super::{
+        char_offset, compare_key, parse_rate, parse_size, uniq, Config, GroupMethod, Terminated,
     };
+    use std::io::{Cursor, Read};
 
-    loop {
-        let bytes = file.read_line(&mut line)?;
-        if bytes == 0 {
-            break;
+    fn config() -> Config {
+        Config {
+            in_files: vec!["-".to_string()],
+            out_file: None,
+            count: false,
+            repeated: false,
+            all_repeated: false,
+            unique: false,
+            ignore_case: false,
+            skip_fields: 0,
+            skip_chars: 0,
+            check_chars: None,
+            global: false,
+            approx: false,
+            fp_rate: 0.01,
+            max_memory: 1024,
+            histogram: None,
+            zero_terminated: false,
+            group: None,
         }
+    }
 
-        if line.trim_end() != previous.trim_end() {
-            print(count, &previous)?;
-            previous = line.clone();
-            count = 0;
+    #[test]
+    fn test_compare_key_plain() {
+        let config = config();
+        assert_eq!(compare_key(b"a b", &config), &b"a b"[..]);
+        assert_eq!(compare_key(b" a ", &config), &b" a "[..]);
+        assert_eq!(compare_key(b"a", &config), &b"a"[..]);
+        assert_ne!(compare_key(b"a", &config), compare_key(b" a", &config));
+    }
+
+    #[test]
+    fn test_compare_key_ignore_case() {
+        let config = Config {
+            ignore_case: true,
+            ..config()
+        };
+        assert_eq!(compare_key(b"Hello", &config), &b"hello"[..]);
+        assert_eq!(compare_key("ÄB".as_bytes(), &config), "äb".as_bytes());
+        assert_eq!(compare_key(b"A\xffB", &config), &b"a\xffb"[..]);
+    }
+
+    #[test]
+    fn test_compare_key_skip_fields() {
+        let config = Config {
+            skip_fields: 2,
+            ..config()
+        };
+        assert_eq!(compare_key(b"1 foo bar", &config), &b" bar"[..]);
+        assert_eq!(
+            compare_key(b"  1\t\tfoo  bar baz", &config),
+            &b"  bar baz"[..]
+        );
+        assert_eq!(compare_key(b"only", &config), &b""[..]);
+    }
+
+    #[test]
+    fn test_compare_key_skip_chars() {
+        let config = Config {
+            skip_chars: 2,
+            ..config()
+        };
+        assert_eq!(compare_key(b"abcd", &config), &b"cd"[..]);
+        assert_eq!(compare_key("éébc".as_bytes(), &config), &b"bc"[..]);
+        assert_eq!(compare_key(b"\xff\xfebc", &config), &b"bc"[..]);
+        assert_eq!(compare_key(b"a", &config), &b""[..]);
+
+        let config = Config {
+            skip_fields: 1,
+            skip_chars: 1,
+            ..config
+        };
+        assert_eq!(compare_key(b"x  abc", &config), &b" abc"[..]);
+    }
+
+    #[test]
+    fn test_compare_key_check_chars() {
+        let config = Config {
+            check_chars: Some(2),
+            ..config()
+        };
+        assert_eq!(compare_key(b"abcd", &config), &b"ab"[..]);
+        assert_eq!(compare_key(b"a", &config), &b"a"[..]);
+
+        let config = Config {
+            skip_chars: 1,
+            check_chars: Some(2),
+            ignore_case: true,
+            ..config
+        };
+        assert_eq!(compare_key(b"xABcd", &config), &b"ab"[..]);
+    }
+
+    #[test]
+    fn test_char_offset() {
+        assert_eq!(char_offset(b"abc", 0), 0);
+        assert_eq!(char_offset(b"abc", 2), 2);
+        assert_eq!(char_offset(b"abc", 5), 3);
+        assert_eq!(char_offset("éa".as_bytes(), 1), 2);
+        assert_eq!(char_offset(b"\xffa\xc3\xa9b", 3), 4);
+    }
+
+    fn run_uniq(input: &[u8], config: &Config) -> Vec<u8> {
+        let mut output = vec![];
+        let res = uniq(&mut Cursor::new(input), &mut output, config);
+        assert!(res.is_ok());
+        output
+    }
+
+    #[test]
+    fn test_uniq_final_newline() {
+        let config = config();
+        assert_eq!(run_uniq(b"", &config), b"");
+        assert_eq!(run_uniq(b"a\na", &config), b"a\n");
+        assert_eq!(run_uniq(b"a\nb", &config), b"a\nb\n");
+
+        let config = Config {
+            count: true,
+            ..config
+        };
+        assert_eq!(run_uniq(b"a\na\nb", &config), b"   2 a\n   1 b\n");
+    }
+
+    #[test]
+    fn test_uniq_invalid_utf8() {
+        let config = config();
+        assert_eq!(run_uniq(b"\xff\n\xff\n\xfe\n", &config), b"\xff\n\xfe\n");
+    }
+
+    #[test]
+    fn test_uniq_zero_terminated() {
+        let config = Config {
+            zero_terminated: true,
+            ..config()
+        };
+        assert_eq!(run_uniq(b"a\nb\0a\nb\0c", &config), b"a\nb\0c\0");
+    }
+
+    #[test]
+    fn test_uniq_group() {
+        let input = b"a\na\nb\nc\nc\n";
+        let expected: [(GroupMethod, &[u8]); 4] = [
+            (GroupMethod::Separate, b"a\na\n\nb\n\nc\nc\n"),
+            (GroupMethod::Prepend, b"\na\na\n\nb\n\nc\nc\n"),
+            (GroupMethod::Append, b"a\na\n\nb\n\nc\nc\n\n"),
+            (GroupMethod::Both, b"\na\na\n\nb\n\nc\nc\n\n"),
+        ];
+        for (method, output) in expected {
+            let config = Config {
+                group: Some(method),
+                ..config()
+            };
+            assert_eq!(run_uniq(input, &config), output, "{:?}", method);
+            assert_eq!(run_uniq(b"", &config), b"");
         }
+    }
 
-        count += 1;
-        line.clear();
+    #[test]
+    fn test_terminated() {
+        let mut buf = vec![];
+        let res = Terminated::new(&b"a\nb"[..], b'\n').read_to_end(&mut buf);
+        assert_eq!(res.unwrap(), 4);
+        assert_eq!(buf, b"a\nb\n");
+
+        let mut buf = vec![];
+        let res = Terminated::new(&b"a\n"[..], b'\n').read_to_end(&mut buf);
+        assert_eq!(res.unwrap(), 2);
+
+        let mut buf = vec![];
+        let res = Terminated::new(&b""[..], b'\n').read_to_end(&mut buf);
+        assert_eq!(res.unwrap(), 0);
     }
 
-    print(count, &previous)?;
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100").unwrap(), 100);
+        assert_eq!(parse_size("2K").unwrap(), 2048);
+        assert_eq!(parse_size("64M").unwrap(), 64 * 1024 * 1024);
+        assert_eq!(parse_size("1g").unwrap(), 1024 * 1024 * 1024);
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("1T").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("99999999999999G").is_err());
+    }
 
-    Ok(())
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("0.01").unwrap(), 0.01);
+        assert!(parse_rate("0").is_err());
+        assert!(parse_rate("1").is_err());
+        assert!(parse_rate("foo").is_err());
+    }
 }
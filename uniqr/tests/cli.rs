@@ -13,6 +13,7 @@ struct Test {
 }
 
 const PRG: &str = "uniqr";
+const OPTIONS: &str = "tests/inputs/options.txt";
 
 const EMPTY: Test = Test {
     input: "tests/inputs/empty.txt",
@@ -108,6 +109,28 @@ fn dies_bad_file() -> TestResult {
     Ok(())
 }
 
+// --------------------------------------------------
+#[test]
+fn dies_bad_skip_fields() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-f", "x", OPTIONS])
+        .assert()
+        .failure()
+        .stderr("invalid number of fields to skip -- x\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_all_repeated_and_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["-D", "-c", OPTIONS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
 // --------------------------------------------------
 // HELPER FUNCTIONS
 fn run(test: &Test) -> TestResult {
@@ -124,7 +147,7 @@ fn run(test: &Test) -> TestResult {
 fn run_count(test: &Test) -> TestResult {
     let expected = fs::read_to_string(test.out_count)?;
     Command::cargo_bin(PRG)?
-        .args(&[test.input, "-c"])
+        .args([test.input, "-c"])
         .assert()
         .success()
         .stdout(expected);
@@ -162,12 +185,12 @@ fn run_outfile(test: &Test) -> TestResult {
     let outfile = NamedTempFile::new()?;
     let outpath = &outfile.path().to_str().unwrap();
     Command::cargo_bin(PRG)?
-        .args(&[test.input, outpath])
+        .args([test.input, outpath])
         .assert()
         .success()
         .stdout("");
 
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
@@ -179,13 +202,13 @@ fn run_outfile_count(test: &Test) -> TestResult {
     let outpath = &outfile.path().to_str().unwrap();
 
     Command::cargo_bin(PRG)?
-        .args(&[test.input, outpath, "--count"])
+        .args([test.input, outpath, "--count"])
         .assert()
         .success()
         .stdout("");
 
     let expected = fs::read_to_string(test.out_count)?;
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
@@ -198,18 +221,29 @@ fn run_stdin_outfile_count(test: &Test) -> TestResult {
     let outpath = &outfile.path().to_str().unwrap();
 
     Command::cargo_bin(PRG)?
-        .args(&["-", outpath, "-c"])
+        .args(["-", outpath, "-c"])
         .write_stdin(input)
         .assert()
         .stdout("");
 
     let expected = fs::read_to_string(test.out_count)?;
-    let contents = fs::read_to_string(&outpath)?;
+    let contents = fs::read_to_string(outpath)?;
     assert_eq!(&expected, &contents);
 
     Ok(())
 }
 
+// --------------------------------------------------
+fn run_args(args: &[&str], expected_file: &str) -> TestResult {
+    let expected = fs::read_to_string(expected_file)?;
+    Command::cargo_bin(PRG)?
+        .args(args)
+        .assert()
+        .success()
+        .stdout(expected);
+    Ok(())
+}
+
 // --------------------------------------------------
 #[test]
 fn empty() -> TestResult {
@@ -605,3 +639,337 @@ fn t6_outfile_count() -> TestResult {
 fn t6_stdin_outfile_count() -> TestResult {
     run_stdin_outfile_count(&T6)
 }
+
+// --------------------------------------------------
+#[test]
+fn options_repeated() -> TestResult {
+    run_args(&["-d", OPTIONS], "tests/expected/options.txt.d.out")
+}
+
+#[test]
+fn options_all_repeated() -> TestResult {
+    run_args(&["-D", OPTIONS], "tests/expected/options.txt.D.out")
+}
+
+#[test]
+fn options_unique() -> TestResult {
+    run_args(&["-u", OPTIONS], "tests/expected/options.txt.u.out")
+}
+
+#[test]
+fn options_ignore_case() -> TestResult {
+    run_args(&["-i", OPTIONS], "tests/expected/options.txt.i.out")
+}
+
+#[test]
+fn options_skip_fields() -> TestResult {
+    run_args(&["-f", "1", OPTIONS], "tests/expected/options.txt.f1.out")
+}
+
+#[test]
+fn options_skip_chars() -> TestResult {
+    run_args(&["-s", "2", OPTIONS], "tests/expected/options.txt.s2.out")
+}
+
+#[test]
+fn options_check_chars() -> TestResult {
+    run_args(&["-w", "2", OPTIONS], "tests/expected/options.txt.w2.out")
+}
+
+#[test]
+fn options_repeated_ignore_case_skip_fields() -> TestResult {
+    run_args(
+        &["-d", "-i", "-f", "1", OPTIONS],
+        "tests/expected/options.txt.d.i.f1.out",
+    )
+}
+
+#[test]
+fn options_all_repeated_ignore_case_skip_fields() -> TestResult {
+    run_args(
+        &["-D", "-i", "-f", "1", OPTIONS],
+        "tests/expected/options.txt.D.i.f1.out",
+    )
+}
+
+#[test]
+fn options_count_unique_skip_fields() -> TestResult {
+    run_args(
+        &["-c", "-u", "-f", "1", OPTIONS],
+        "tests/expected/options.txt.c.u.f1.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn three_global() -> TestResult {
+    run_args(
+        &["--global", THREE.input],
+        "tests/expected/three.txt.global.out",
+    )
+}
+
+#[test]
+fn three_global_count() -> TestResult {
+    run_args(
+        &["--global", "-c", THREE.input],
+        "tests/expected/three.txt.global.c.out",
+    )
+}
+
+#[test]
+fn three_global_all_repeated() -> TestResult {
+    run_args(
+        &["--global", "-D", THREE.input],
+        "tests/expected/three.txt.global.D.out",
+    )
+}
+
+#[test]
+fn three_approx() -> TestResult {
+    run_args(
+        &["--approx", "--max-memory", "1K", THREE.input],
+        "tests/expected/three.txt.global.out",
+    )
+}
+
+#[test]
+fn dies_approx_and_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--approx", "-c", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
+
+#[test]
+fn dies_bad_fp_rate() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--approx", "--fp-rate", "1.5", THREE.input])
+        .assert()
+        .failure()
+        .stderr("invalid false-positive rate -- 1.5\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn dies_max_memory_overflow() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--approx", "--max-memory", "99999999999999G", THREE.input])
+        .assert()
+        .failure()
+        .code(1)
+        .stderr("invalid memory size -- 99999999999999G\n");
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn three_histogram() -> TestResult {
+    run_args(
+        &["--histogram", THREE.input],
+        "tests/expected/three.txt.histogram.out",
+    )
+}
+
+#[test]
+fn three_histogram_top_percent_bar() -> TestResult {
+    run_args(
+        &[
+            "--histogram",
+            "--top",
+            "3",
+            "--percent",
+            "--bar",
+            THREE.input,
+        ],
+        "tests/expected/three.txt.histogram.top3.percent.bar.out",
+    )
+}
+
+#[test]
+fn options_histogram_sort_key_field() -> TestResult {
+    run_args(
+        &[
+            "--histogram",
+            "--sort",
+            "key",
+            "--field",
+            "2",
+            "-i",
+            OPTIONS,
+        ],
+        "tests/expected/options.txt.histogram.key.field2.i.out",
+    )
+}
+
+#[test]
+fn options_histogram_regex() -> TestResult {
+    run_args(
+        &["--histogram", "--regex", r"^(\w+) ", OPTIONS],
+        "tests/expected/options.txt.histogram.regex.out",
+    )
+}
+
+#[test]
+fn histogram_skip_fields_chars() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "-f", "1"])
+        .write_stdin("x a\ny a\nz b\n")
+        .assert()
+        .success()
+        .stdout("   2  a\n   1  b\n");
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "-s", "1", "-w", "2"])
+        .write_stdin("xab1\nyab2\nzcd\n")
+        .assert()
+        .success()
+        .stdout("   2 ab\n   1 cd\n");
+    Ok(())
+}
+
+#[test]
+fn histogram_zero_terminated() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "-z"])
+        .write_stdin(&b"a\xff\0b\nc\0a\xff\0"[..])
+        .assert()
+        .success()
+        .stdout(&b"   2 a\xff\0   1 b\nc\0"[..]);
+    Ok(())
+}
+
+#[test]
+fn dies_histogram_bad_regex() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--histogram", "--regex", "*", OPTIONS])
+        .assert()
+        .failure()
+        .stderr("Invalid --regex \"*\"\n");
+    Ok(())
+}
+
+#[test]
+fn dies_top_without_histogram() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--top", "2", OPTIONS])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--histogram"));
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn zero_terminated() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("-z")
+        .write_stdin("a\0a\0b\nc\0b\nc")
+        .assert()
+        .success()
+        .stdout("a\0b\nc\0");
+    Ok(())
+}
+
+#[test]
+fn invalid_utf8() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .arg("-c")
+        .write_stdin(&b"\xff\n\xff\nok\n"[..])
+        .assert()
+        .success()
+        .stdout(&b"   2 \xff\n   1 ok\n"[..]);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn multiple_inputs_count() -> TestResult {
+    run_args(
+        &["-c", "--concat", T3.input, T1.input, TWO.input],
+        "tests/expected/t3_t1_two.c.out",
+    )
+}
+
+#[test]
+fn multiple_inputs_dies_bad_file() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("{}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args(["--concat", ONE.input, &bad])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::is_match(expected)?);
+    Ok(())
+}
+
+#[test]
+fn multiple_inputs_output_option() -> TestResult {
+    let outfile = NamedTempFile::new()?;
+    let outpath = &outfile.path().to_str().unwrap();
+    Command::cargo_bin(PRG)?
+        .args([
+            "-c", "--concat", T3.input, T1.input, TWO.input, "-o", outpath,
+        ])
+        .assert()
+        .success()
+        .stdout("");
+    let expected = fs::read_to_string("tests/expected/t3_t1_two.c.out")?;
+    let contents = fs::read_to_string(outpath)?;
+    assert_eq!(&expected, &contents);
+    Ok(())
+}
+
+#[test]
+fn dies_extra_operand() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([T3.input, T1.input, TWO.input])
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(predicate::str::contains(format!(
+            "extra operand '{}'",
+            TWO.input
+        )));
+    Ok(())
+}
+
+#[test]
+fn dies_outfile_and_output() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args([ONE.input, "out.txt", "-o", "other.txt"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be given with --output"));
+    assert!(fs::metadata("out.txt").is_err());
+    Ok(())
+}
+
+#[test]
+fn three_group() -> TestResult {
+    run_args(
+        &["--group", THREE.input],
+        "tests/expected/three.txt.group.out",
+    )
+}
+
+#[test]
+fn three_group_both() -> TestResult {
+    run_args(
+        &["--group=both", THREE.input],
+        "tests/expected/three.txt.group.both.out",
+    )
+}
+
+#[test]
+fn dies_group_and_count() -> TestResult {
+    Command::cargo_bin(PRG)?
+        .args(["--group", "-c", THREE.input])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use clap::{App, Arg};
+use serde::Serialize;
 use std::error::Error;
 use std::fs::File;
 use std::io::{self, BufRead, BufReader};
+use tabular::{Row, Table};
 
 type MyResult<T> = Result<T, Box<dyn Error>>;
 
@@ -10,61 +12,65 @@ pub struct Config {
     files: Vec<String>,
     lines: bool,
     words: bool,
-    bytes: bool,
     chars: bool,
+    bytes: bool,
+    stats: bool,
+    format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+    Tsv,
 }
 
 pub fn run(config: Config) -> MyResult<()> {
-    // println!("{:?}", config);
-    //
-    let mut total_lines = 0;
-    let mut total_words = 0;
-    let mut total_bytes = 0;
-    let mut total_chars = 0;
+    let mut total = FileInfo::default();
+    let mut counted = vec![];
+    let mut records = vec![];
 
     for filename in &config.files {
-        // println!("{}", filename);
-
-        match open(&filename) {
-            Ok(file) => {
-                if let Ok(FileInfo {
-                    num_lines,
-                    num_words,
-                    num_bytes,
-                    num_chars,
-                }) = count(file)
-                {
-                    total_lines += num_lines;
-                    total_words += num_words;
-                    total_bytes += num_bytes;
-                    total_chars += num_chars;
-
-                    println!(
-                        "{}{}{}{}{}",
-                        format_field(num_lines, config.lines),
-                        format_field(num_words, config.words),
-                        format_field(num_bytes, config.bytes),
-                        format_field(num_chars, config.chars),
-                        if filename == "-" {
-                            "".to_string()
-                        } else {
-                            format!(" {}", filename)
-                        }
-                    )
-                }
+        match open(filename).and_then(count) {
+            Ok(info) => {
+                total.add(&info);
+                records.push(Record::file(filename, &info, config.stats));
+                counted.push((filename, info));
+            }
+            Err(e) => {
+                eprintln!("{}: {}", filename, e);
+                records.push(Record::error(filename, e.as_ref()));
             }
-            Err(e) => eprintln!("{}: {}", filename, e),
         }
     }
 
-    if config.files.len() > 1 {
-        println!(
-            "{}{}{}{} total",
-            format_field(total_lines, config.lines),
-            format_field(total_words, config.words),
-            format_field(total_bytes, config.bytes),
-            format_field(total_chars, config.chars),
-        );
+    match config.format {
+        OutputFormat::Text if config.stats => {
+            print!("{}", format_stats(&counted, &total));
+        }
+        OutputFormat::Text => {
+            let width = column_width(&config, &total);
+            for (filename, info) in &counted {
+                println!(
+                    "{}{}",
+                    format_counts(info, &config, width),
+                    if *filename == "-" {
+                        "".to_string()
+                    } else {
+                        format!(" {}", filename)
+                    }
+                );
+            }
+
+            if config.files.len() > 1 {
+                println!("{} total", format_counts(&total, &config, width));
+            }
+        }
+        format => {
+            records.push(Record::total(&total, config.stats));
+            print!("{}", format_records(&records, format, config.stats)?);
+        }
     }
     Ok(())
 }
@@ -111,25 +117,35 @@ pub fn get_args() -> MyResult<Config> {
                 .help(chars_help)
                 .conflicts_with("bytes"),
         )
+        .arg(
+            Arg::with_name("stats")
+                .long("stats")
+                .help("Show character-class statistics"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .help("Output format")
+                .possible_values(&["text", "json", "csv", "tsv"])
+                .default_value("text"),
+        )
         .get_matches();
 
     let files = matches.values_of_lossy("files").unwrap();
     let mut lines = matches.is_present("lines");
     let mut words = matches.is_present("words");
-    let mut bytes = matches.is_present("bytes");
     let chars = matches.is_present("chars");
+    let mut bytes = matches.is_present("bytes");
+    let stats = matches.is_present("stats");
+    let format = match matches.value_of("format") {
+        Some("json") => OutputFormat::Json,
+        Some("csv") => OutputFormat::Csv,
+        Some("tsv") => OutputFormat::Tsv,
+        _ => OutputFormat::Text,
+    };
 
-    if !lines && !words && !bytes && !chars {
-        return Ok(Config {
-            files,
-            lines: true,
-            words: true,
-            bytes: true,
-            chars,
-        });
-    }
-
-    if [lines, words, bytes, chars].iter().all(|v| !v) {
+    if [lines, words, chars, bytes].iter().all(|v| !v) {
         lines = true;
         words = true;
         bytes = true;
@@ -139,45 +155,270 @@ pub fn get_args() -> MyResult<Config> {
         files,
         lines,
         words,
-        bytes,
         chars,
+        bytes,
+        stats,
+        format,
     })
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct FileInfo {
     num_lines: usize,
     num_words: usize,
     num_bytes: usize,
     num_chars: usize,
+    num_ascii: usize,
+    num_non_ascii: usize,
+    num_invalid: usize,
+    num_crlf: usize,
+    num_lf: usize,
+    num_tabs: usize,
+    num_trailing_ws: usize,
+    missing_newline: bool,
 }
 
-pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
-    let mut num_lines = 0;
-    let mut num_words = 0;
-    let mut num_bytes = 0;
-    let mut num_chars = 0;
+impl FileInfo {
+    fn add(&mut self, other: &FileInfo) {
+        self.num_lines += other.num_lines;
+        self.num_words += other.num_words;
+        self.num_bytes += other.num_bytes;
+        self.num_chars += other.num_chars;
+        self.num_ascii += other.num_ascii;
+        self.num_non_ascii += other.num_non_ascii;
+        self.num_invalid += other.num_invalid;
+        self.num_crlf += other.num_crlf;
+        self.num_lf += other.num_lf;
+        self.num_tabs += other.num_tabs;
+        self.num_trailing_ws += other.num_trailing_ws;
+        self.missing_newline |= other.missing_newline;
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Stats {
+    ascii: usize,
+    non_ascii: usize,
+    invalid: usize,
+    crlf: usize,
+    lf: usize,
+    tabs: usize,
+    trailing_whitespace: usize,
+    missing_newline: bool,
+}
+
+impl From<&FileInfo> for Stats {
+    fn from(info: &FileInfo) -> Self {
+        Stats {
+            ascii: info.num_ascii,
+            non_ascii: info.num_non_ascii,
+            invalid: info.num_invalid,
+            crlf: info.num_crlf,
+            lf: info.num_lf,
+            tabs: info.num_tabs,
+            trailing_whitespace: info.num_trailing_ws,
+            missing_newline: info.missing_newline,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Record {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    chars: Option<usize>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    stats: Option<Stats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Record {
+    fn counts(kind: &'static str, file: Option<String>, info: &FileInfo, stats: bool) -> Self {
+        Record {
+            kind,
+            file,
+            lines: Some(info.num_lines),
+            words: Some(info.num_words),
+            bytes: Some(info.num_bytes),
+            chars: Some(info.num_chars),
+            stats: if stats { Some(Stats::from(info)) } else { None },
+            error: None,
+        }
+    }
+
+    fn file(filename: &str, info: &FileInfo, stats: bool) -> Self {
+        Record::counts("file", Some(filename.to_string()), info, stats)
+    }
+
+    fn total(info: &FileInfo, stats: bool) -> Self {
+        Record::counts("total", None, info, stats)
+    }
 
-    let mut line = String::new();
+    fn error(filename: &str, e: &dyn Error) -> Self {
+        Record {
+            kind: "error",
+            file: Some(filename.to_string()),
+            lines: None,
+            words: None,
+            bytes: None,
+            chars: None,
+            stats: None,
+            error: Some(e.to_string()),
+        }
+    }
+}
+
+fn format_records(records: &[Record], format: OutputFormat, stats: bool) -> MyResult<String> {
+    let delimiter = match format {
+        OutputFormat::Json => {
+            return Ok(format!("{}\n", serde_json::to_string_pretty(records)?));
+        }
+        OutputFormat::Tsv => b'\t',
+        _ => b',',
+    };
+
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(vec![]);
+    let mut header = vec!["type", "file", "lines", "words", "bytes", "chars"];
+    if stats {
+        header.extend(STATS_HEADER);
+    }
+    header.push("error");
+    wtr.write_record(header)?;
+
+    for record in records {
+        let num = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+        let mut row = vec![
+            record.kind.to_string(),
+            record.file.clone().unwrap_or_default(),
+            num(record.lines),
+            num(record.words),
+            num(record.bytes),
+            num(record.chars),
+        ];
+        if stats {
+            match &record.stats {
+                Some(s) => row.extend(stats_row(s)),
+                None => row.extend(STATS_HEADER.iter().map(|_| String::new())),
+            }
+        }
+        row.push(record.error.clone().unwrap_or_default());
+        wtr.write_record(row)?;
+    }
+    Ok(String::from_utf8(wtr.into_inner()?)?)
+}
+
+const STATS_HEADER: [&str; 8] = [
+    "ascii",
+    "non_ascii",
+    "invalid",
+    "crlf",
+    "lf",
+    "tabs",
+    "trailing_whitespace",
+    "missing_newline",
+];
+
+fn stats_row(stats: &Stats) -> Vec<String> {
+    vec![
+        stats.ascii.to_string(),
+        stats.non_ascii.to_string(),
+        stats.invalid.to_string(),
+        stats.crlf.to_string(),
+        stats.lf.to_string(),
+        stats.tabs.to_string(),
+        stats.trailing_whitespace.to_string(),
+        if stats.missing_newline { "yes" } else { "no" }.to_string(),
+    ]
+}
+
+fn format_stats(counted: &[(&String, FileInfo)], total: &FileInfo) -> String {
+    let mut table = Table::new("{:>}  {:>}  {:>}  {:>}  {:>}  {:>}  {:>}  {:>}  {:<}");
+    table.add_row(
+        STATS_HEADER
+            .iter()
+            .chain(["file"].iter())
+            .fold(Row::new(), |row, name| row.with_cell(name)),
+    );
+
+    let mut add = |info: &FileInfo, name: &str| {
+        table.add_row(
+            stats_row(&Stats::from(info))
+                .into_iter()
+                .fold(Row::new(), |row, cell| row.with_cell(cell))
+                .with_cell(name),
+        );
+    };
+    for (filename, info) in counted {
+        add(info, filename);
+    }
+    if counted.len() > 1 {
+        add(total, "total");
+    }
+
+    format!("{}", table)
+}
+
+pub fn count(mut file: impl BufRead) -> MyResult<FileInfo> {
+    let mut info = FileInfo::default();
+    let mut line = vec![];
 
     loop {
-        let bytes_read = file.read_line(&mut line)?;
+        let bytes_read = file.read_until(b'\n', &mut line)?;
         if bytes_read == 0 {
             break;
         }
-        num_lines += 1;
-        num_bytes += bytes_read;
-        num_words += line.split_whitespace().count();
-        num_chars += line.chars().count();
+        info.num_lines += 1;
+        info.num_bytes += bytes_read;
+
+        let mut content = &line[..];
+        if let Some(rest) = content.strip_suffix(b"\n") {
+            content = rest;
+            match content.strip_suffix(b"\r") {
+                Some(rest) => {
+                    content = rest;
+                    info.num_crlf += 1;
+                }
+                None => info.num_lf += 1,
+            }
+        } else {
+            info.missing_newline = true;
+        }
+
+        info.num_tabs += content.iter().filter(|&&b| b == b'\t').count();
+        if content.ends_with(b" ") || content.ends_with(b"\t") {
+            info.num_trailing_ws += 1;
+        }
+
+        info.num_words += String::from_utf8_lossy(&line).split_whitespace().count();
+        for chunk in line.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                info.num_chars += 1;
+                if c.is_ascii() {
+                    info.num_ascii += 1;
+                } else {
+                    info.num_non_ascii += 1;
+                }
+            }
+            if !chunk.invalid().is_empty() {
+                info.num_invalid += 1;
+            }
+        }
         line.clear();
     }
 
-    Ok(FileInfo {
-        num_lines,
-        num_words,
-        num_bytes,
-        num_chars,
-    })
+    Ok(info)
 }
 
 fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
@@ -187,17 +428,46 @@ fn open(filename: &str) -> MyResult<Box<dyn BufRead>> {
     }
 }
 
-fn format_field(value: usize, show: bool) -> String {
-    if show {
-        format!("{:>8}", value)
+fn format_field(value: usize, width: usize) -> String {
+    format!("{:>width$}", value, width = width)
+}
+
+fn format_counts(info: &FileInfo, config: &Config, width: usize) -> String {
+    [
+        (info.num_lines, config.lines),
+        (info.num_words, config.words),
+        (info.num_chars, config.chars),
+        (info.num_bytes, config.bytes),
+    ]
+    .iter()
+    .filter(|(_, show)| *show)
+    .map(|(value, _)| format_field(*value, width))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn column_width(config: &Config, total: &FileInfo) -> usize {
+    let shown = [config.lines, config.words, config.chars, config.bytes]
+        .iter()
+        .filter(|v| **v)
+        .count();
+    if config.files.len() == 1 && shown == 1 {
+        return 1;
+    }
+
+    let width = total.num_bytes.to_string().len();
+    if config.files.iter().any(|f| f == "-") {
+        width.max(7)
     } else {
-        "".to_string()
+        width
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{count, format_field, FileInfo};
+    use super::{
+        column_width, count, format_field, format_records, Config, FileInfo, OutputFormat, Record,
+    };
     use std::io::Cursor;
 
     #[test]
@@ -211,14 +481,105 @@ mod tests {
             num_words: 10,
             num_chars: 48,
             num_bytes: 48,
+            num_ascii: 48,
+            num_crlf: 1,
+            ..Default::default()
+        };
+        assert_eq!(info.unwrap(), expected);
+    }
+
+    #[test]
+    fn test_count_stats() {
+        let text: &[u8] = b"a\tb \r\nh\xc3\xa9llo\xff\xfe x\nend";
+        let info = count(Cursor::new(text));
+        assert!(info.is_ok());
+
+        let expected = FileInfo {
+            num_lines: 3,
+            num_words: 5,
+            num_chars: 17,
+            num_bytes: 20,
+            num_ascii: 16,
+            num_non_ascii: 1,
+            num_invalid: 2,
+            num_crlf: 1,
+            num_lf: 1,
+            num_tabs: 1,
+            num_trailing_ws: 1,
+            missing_newline: true,
         };
         assert_eq!(info.unwrap(), expected);
     }
 
     #[test]
     fn test_format_field() {
-        assert_eq!(format_field(1, false), "");
-        assert_eq!(format_field(3, true), "       3");
-        assert_eq!(format_field(10, true), "      10");
+        assert_eq!(format_field(1, 1), "1");
+        assert_eq!(format_field(3, 8), "       3");
+        assert_eq!(format_field(10, 3), " 10");
+    }
+
+    #[test]
+    fn test_column_width() {
+        let total = FileInfo {
+            num_lines: 5,
+            num_words: 38,
+            num_bytes: 225,
+            num_chars: 207,
+            ..Default::default()
+        };
+        let mut config = Config {
+            files: vec!["a.txt".to_string(), "b.txt".to_string()],
+            lines: true,
+            words: false,
+            chars: false,
+            bytes: false,
+            stats: false,
+            format: OutputFormat::Text,
+        };
+        assert_eq!(column_width(&config, &total), 3);
+
+        config.files = vec!["-".to_string(), "b.txt".to_string()];
+        assert_eq!(column_width(&config, &total), 7);
+
+        config.files = vec!["-".to_string()];
+        assert_eq!(column_width(&config, &total), 1);
+
+        config.words = true;
+        assert_eq!(column_width(&config, &total), 7);
+    }
+
+    #[test]
+    fn test_format_records() {
+        let info = FileInfo {
+            num_lines: 1,
+            num_words: 2,
+            num_bytes: 3,
+            num_chars: 3,
+            ..Default::default()
+        };
+        let err: Box<dyn std::error::Error> = From::from("boom");
+        let records = vec![
+            Record::file("a,b.txt", &info, false),
+            Record::error("bad", err.as_ref()),
+            Record::total(&info, false),
+        ];
+
+        let res = format_records(&records, OutputFormat::Csv, false);
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            "type,file,lines,words,bytes,chars,error\n\
+             file,\"a,b.txt\",1,2,3,3,\n\
+             error,bad,,,,,boom\n\
+             total,,1,2,3,3,\n"
+        );
+
+        let res = format_records(&records[1..2], OutputFormat::Json, false);
+        assert!(res.is_ok());
+        assert_eq!(
+            res.unwrap(),
+            "[\n  {\n    \"type\": \"error\",\n    \"file\": \"bad\",\n    \
+             \"error\": \"boom\"\n  }\n]\n"
+        );
     }
 }
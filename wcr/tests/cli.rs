@@ -1,3 +1,6 @@
+// The tests keep the `.args(&[..])` style they were written in
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use predicates::prelude::*;
 use rand::{distributions::Alphanumeric, Rng};
@@ -212,3 +215,81 @@ fn test_all_words_lines() -> TestResult {
 fn test_all_bytes_lines() -> TestResult {
     run(&["-cl", EMPTY, FOX, ATLAMAL], "tests/expected/all.cl.out")
 }
+
+// --------------------------------------------------
+#[test]
+fn test_all_chars() -> TestResult {
+    run(&["-m", EMPTY, FOX, ATLAMAL], "tests/expected/all.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_lines_words_chars() -> TestResult {
+    run(&["-lwm", EMPTY, FOX, ATLAMAL], "tests/expected/all.lwm.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn atlamal_chars() -> TestResult {
+    run(&["-m", ATLAMAL], "tests/expected/atlamal.txt.m.out")
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_json() -> TestResult {
+    run(
+        &["--format", "json", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.json.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_csv() -> TestResult {
+    run(
+        &["--format", "csv", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.csv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_tsv() -> TestResult {
+    run(
+        &["--format", "tsv", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.tsv.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn bad_file_error_record() -> TestResult {
+    let bad = gen_bad_file();
+    let expected = format!("error,{},,,,,.* [(]os error 2[)]", bad);
+    let message = format!("{}: .* [(]os error 2[)]", bad);
+    Command::cargo_bin(PRG)?
+        .args(&["--format", "csv", FOX, &bad])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(expected)?)
+        .stderr(predicate::str::is_match(message)?);
+    Ok(())
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_stats() -> TestResult {
+    run(
+        &["--stats", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.stats.out",
+    )
+}
+
+// --------------------------------------------------
+#[test]
+fn test_all_stats_csv() -> TestResult {
+    run(
+        &["--stats", "--format", "csv", EMPTY, FOX, ATLAMAL],
+        "tests/expected/all.stats.csv.out",
+    )
+}